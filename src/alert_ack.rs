@@ -0,0 +1,109 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use crate::config::{Config};
+use crate::alert_state_file::{get_alert_identity, load_alert_states};
+
+use std::io::Write;
+
+// Acknowledgements are written by 'cryptmon alerts ack' to the ack file as one rule filter per line (an empty
+// line acknowledges all escalating alerts), and picked up (and removed) by the running alert service.
+
+pub fn write_alert_acknowledgement(path: &str, rule_filter: &str) -> Result<(), String> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?;
+    writeln!(file, "{}", get_alert_identity(rule_filter)).map_err(|e| e.to_string())?;
+    return Ok(());
+}
+
+// Returns any pending acknowledgement rule filters, removing them from the file.
+pub fn take_alert_acknowledgements(path: &str) -> Vec<String> {
+    // move it out of the way first, so any acknowledgements written while we're reading it aren't lost
+    let taken_path = format!("{}.taken", path);
+    if std::fs::rename(path, &taken_path).is_err() {
+        // there aren't any
+        return Vec::new();
+    }
+
+    let contents = std::fs::read_to_string(&taken_path);
+    let _ = std::fs::remove_file(&taken_path);
+    if let Err(err) = contents {
+        eprintln!("Error: Couldn't read alert acknowledgements file: '{}'. Error: {}", path, err);
+        return Vec::new();
+    }
+
+    return contents.unwrap().lines().map(|line| line.trim().to_string()).collect();
+}
+
+// whether the alert (identity) is acknowledged by the filter, which is part of the rule text, or empty for all alerts
+pub fn does_acknowledgement_match(alert_identity: &str, rule_filter: &str) -> bool {
+    return alert_identity.contains(rule_filter);
+}
+
+// cryptmon alerts ack [<rule text>]
+pub fn run_alerts_ack(config: &Config, args: &[String]) {
+    if config.alert_config.ack_file_path.is_none() {
+        eprintln!("Error: No alert acknowledgements file is configured, see 'alerts.ackFile'.");
+        return;
+    }
+    let ack_file_path = config.alert_config.ack_file_path.as_ref().unwrap();
+
+    let rule_filter = get_alert_identity(&args.join(" "));
+
+    // if we can, show which alerts are currently escalating, so it's clear whether it matched anything
+    if let Some(state_file_path) = &config.alert_config.state_file_path {
+        let escalating_alerts: Vec<String> = load_alert_states(state_file_path).into_iter()
+                                                .filter(|(identity, state)| state.next_escalation_time.is_some() &&
+                                                                            does_acknowledgement_match(identity, &rule_filter))
+                                                .map(|(identity, _)| identity).collect();
+        if escalating_alerts.is_empty() {
+            println!("No escalating alerts match: '{}'.", rule_filter);
+            return;
+        }
+
+        println!("Acknowledging escalating alerts:");
+        for identity in escalating_alerts {
+            println!("  {}", identity);
+        }
+    }
+
+    if let Err(err) = write_alert_acknowledgement(ack_file_path, &rule_filter) {
+        eprintln!("Error: Couldn't write to alert acknowledgements file: '{}'. Error: {}", ack_file_path, err);
+        return;
+    }
+    println!("The acknowledgement will be picked up by 'cryptmon alerts' at its next check.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acknowledgements_round_trip_01() {
+        let path = std::env::temp_dir().join(format!("cryptmon_alert_acks_test_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        assert!(take_alert_acknowledgements(path).is_empty());
+
+        write_alert_acknowledgement(path, "alert(BTC,  <=").unwrap();
+        write_alert_acknowledgement(path, "").unwrap();
+        let filters = take_alert_acknowledgements(path);
+        assert_eq!(filters, vec!["alert(BTC, <=".to_string(), "".to_string()]);
+        assert!(take_alert_acknowledgements(path).is_empty());
+
+        assert!(does_acknowledgement_match("alert(BTC, <=, 40000, pushsafer, severity=critical)", &filters[0]));
+        assert!(!does_acknowledgement_match("alert(ETH, <=, 2500, pushsafer)", &filters[0]));
+        assert!(does_acknowledgement_match("alert(ETH, <=, 2500, pushsafer)", &filters[1]));
+    }
+}
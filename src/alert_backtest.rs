@@ -0,0 +1,165 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use crate::config::{Config};
+use crate::alert_service::{AlertService};
+use crate::alert_history::{AlertOutcome, print_alert_history_table};
+use crate::price_provider::{PriceProviderParams};
+use crate::price_recording::{RecordedPriceProvider, load_price_records};
+use crate::cli_table_printer::{CLITablePrinter, Alignment};
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+
+// Replays recorded prices (see 'alerts.priceRecordFile') through the configured alerts with a simulated clock,
+// printing which alerts would have fired and when:
+// cryptmon alerts backtest --from <date> --to <date> [--prices <file>] [--all]
+pub fn run_alerts_backtest(config: &Config, args: &[String]) {
+    let usage = "Usage: cryptmon alerts backtest --from <date> --to <date> [--prices <file>] [--all]";
+
+    let mut start_time = None;
+    let mut end_time = None;
+    let mut prices_path = config.alert_config.price_record_file_path.clone();
+    let mut show_all = false;
+
+    let mut arg_index = 0;
+    while arg_index < args.len() {
+        let option = args[arg_index].as_str();
+        if option == "--all" {
+            show_all = true;
+            arg_index += 1;
+            continue;
+        }
+
+        let value = args.get(arg_index + 1);
+        if value.is_none() {
+            eprintln!("Error: Missing value for: '{}'. {}", option, usage);
+            return;
+        }
+        let value = value.unwrap();
+
+        match option {
+            "--from" | "--to" => {
+                let time = parse_backtest_time(value);
+                if time.is_none() {
+                    eprintln!("Error: Invalid date: '{}', it should be in the form '2024-01-01' or '2024-01-01T09:00:00+00:00'.", value);
+                    return;
+                }
+                if option == "--from" {
+                    start_time = time;
+                }
+                else {
+                    end_time = time;
+                }
+            },
+            "--prices" => prices_path = Some(value.to_string()),
+            _ => {
+                eprintln!("Error: Unknown option: '{}'. {}", option, usage);
+                return;
+            }
+        }
+        arg_index += 2;
+    }
+
+    if start_time.is_none() || end_time.is_none() {
+        eprintln!("Error: Both '--from' and '--to' need to be specified. {}", usage);
+        return;
+    }
+    let (start_time, end_time) = (start_time.unwrap(), end_time.unwrap());
+
+    if prices_path.is_none() {
+        eprintln!("Error: No price record file to replay, either enable 'alerts.priceRecordFile' or use '--prices'.");
+        return;
+    }
+    let prices_path = prices_path.unwrap();
+
+    let price_records = load_price_records(&prices_path, start_time, end_time);
+    if let Err(err) = price_records {
+        eprintln!("Error: Couldn't read price record file: '{}'. Error: {}", prices_path, err);
+        return;
+    }
+    let price_records = price_records.unwrap();
+    if price_records.is_empty() {
+        println!("No recorded prices found between {} and {} in: '{}'.", start_time.format("%Y-%m-%d %H:%M"), end_time.format("%Y-%m-%d %H:%M"),
+                 prices_path);
+        return;
+    }
+
+    // we don't want the backtest to touch any of the files the real alert service uses
+    let mut backtest_config = config.clone();
+    backtest_config.alert_config.state_file_path = None;
+    backtest_config.alert_config.history_file_path = None;
+    backtest_config.alert_config.ack_file_path = None;
+    backtest_config.alert_config.usage_file_path = None;
+    backtest_config.alert_config.price_record_file_path = None;
+
+    let mut provider_params = PriceProviderParams::new();
+    provider_params.fiat_currency = config.alert_config.fiat_currency.clone();
+    provider_params.coin_name_ignore_items = config.alert_config.coin_name_ignore_items.clone();
+
+    let price_provider = Box::new(RecordedPriceProvider::new(&price_records));
+    let alert_service = AlertService::new(&backtest_config, &provider_params, price_provider);
+    if alert_service.is_none() {
+        eprintln!("Error creating AlertService");
+        return;
+    }
+
+    let check_count = price_records.len();
+    let history_records = alert_service.unwrap().run_backtest(price_records);
+
+    // per rule: fired count, suppressed count
+    let mut rule_counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for record in &history_records {
+        let counts = rule_counts.entry(&record.rule).or_insert((0, 0));
+        if is_fired_outcome(record.outcome) {
+            counts.0 += 1;
+        }
+        else {
+            counts.1 += 1;
+        }
+    }
+
+    let shown_records: Vec<_> = history_records.iter().filter(|r| show_all || is_fired_outcome(r.outcome)).cloned().collect();
+    if shown_records.is_empty() {
+        println!("No alerts would have fired.");
+    }
+    else {
+        print_alert_history_table(&shown_records);
+    }
+
+    println!("Replayed {} price checks from {} to {}.", check_count, start_time.format("%Y-%m-%d %H:%M"), end_time.format("%Y-%m-%d %H:%M"));
+    if !rule_counts.is_empty() {
+        let mut table = CLITablePrinter::new(3);
+        table.add_titles(["Rule", "Fired", "Suppressed"]);
+        table.set_alignment_multiple(&[1usize, 2], Alignment::Right);
+        for (rule, (fired_count, suppressed_count)) in rule_counts {
+            table.add_row_strings(&[rule, &fired_count.to_string(), &suppressed_count.to_string()]);
+        }
+        println!("{}", table);
+    }
+}
+
+fn is_fired_outcome(outcome: AlertOutcome) -> bool {
+    return outcome == AlertOutcome::Fired || outcome == AlertOutcome::Escalated;
+}
+
+// either a date (midnight local time), or an RFC 3339 time
+fn parse_backtest_time(str_val: &str) -> Option<DateTime<Local>> {
+    if let Ok(date) = NaiveDate::parse_from_str(str_val, "%Y-%m-%d") {
+        return Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest();
+    }
+    return DateTime::parse_from_rfc3339(str_val).ok().map(|t| t.with_timezone(&Local));
+}
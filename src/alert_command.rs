@@ -0,0 +1,125 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+// how often to check whether the command has finished yet
+const POLL_INTERVAL_MS: u64 = 50;
+
+// Splits a command string into the path and args, with whitespace separating items,
+// unless within double quotes (so that paths with spaces can be used).
+pub fn split_command_line(command_line: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current_item = String::new();
+    let mut in_quotes = false;
+    let mut have_item = false;
+
+    for c in command_line.chars() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+            have_item = true;
+        }
+        else if c.is_whitespace() && !in_quotes {
+            if have_item {
+                items.push(std::mem::take(&mut current_item));
+                have_item = false;
+            }
+        }
+        else {
+            current_item.push(c);
+            have_item = true;
+        }
+    }
+
+    if have_item {
+        items.push(current_item);
+    }
+
+    return items;
+}
+
+// Runs the command (with the given extra environment variables set) to completion, killing it if it
+// takes longer than timeout (in seconds). stdout/stderr are passed through to ours.
+// Returns an error description if it couldn't be run, timed out, or exited with a non-zero status.
+pub fn run_alert_command(command_line: &str, env_vars: &[(&str, String)], timeout: u64) -> Result<(), String> {
+    let items = split_command_line(command_line);
+    if items.is_empty() {
+        return Err("no command specified".to_string());
+    }
+
+    let mut command = Command::new(&items[0]);
+    command.args(&items[1..]).stdin(Stdio::null());
+    for (name, val) in env_vars {
+        command.env(name, val);
+    }
+
+    let child = command.spawn();
+    if let Err(err) = child {
+        return Err(format!("couldn't start command '{}': {}", items[0], err));
+    }
+    let mut child = child.unwrap();
+
+    let start_time = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                if status.success() {
+                    return Ok(());
+                }
+                return Err(format!("command '{}' failed with {}", items[0], status));
+            },
+            Ok(None) => {
+                if start_time.elapsed() >= Duration::from_secs(timeout) {
+                    // we don't really care if this fails, as it might have only just exited...
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("command '{}' timed out after {} seconds, so was killed", items[0], timeout));
+                }
+                std::thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+            },
+            Err(err) => {
+                return Err(format!("error waiting for command '{}': {}", items[0], err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command_line_01() {
+        assert_eq!(split_command_line("/usr/bin/notify.sh  -v  alert"), vec!["/usr/bin/notify.sh", "-v", "alert"]);
+        assert_eq!(split_command_line("\"/opt/my scripts/alert.sh\" \"\" x"), vec!["/opt/my scripts/alert.sh", "", "x"]);
+        assert!(split_command_line("   ").is_empty());
+    }
+
+    #[test]
+    fn test_run_alert_command_01() {
+        assert_eq!(run_alert_command("true", &[], 1), Ok(()));
+        assert!(run_alert_command("false", &[], 1).unwrap_err().contains("failed with"));
+        assert!(run_alert_command("/nonexistent/alert.sh", &[], 1).unwrap_err().contains("couldn't start command"));
+
+        let start_time = Instant::now();
+        assert!(run_alert_command("sleep 5", &[], 1).unwrap_err().contains("timed out after 1 seconds"));
+        assert!(start_time.elapsed() < Duration::from_secs(4));
+
+        let env_vars = [("CRYPTMON_ALERT_SYMBOL", "BTC".to_string())];
+        assert_eq!(run_alert_command("sh -c \"test $CRYPTMON_ALERT_SYMBOL = BTC\"", &env_vars, 1), Ok(()));
+        assert!(run_alert_command("sh -c \"test $CRYPTMON_ALERT_SYMBOL = ETH\"", &env_vars, 1).is_err());
+    }
+}
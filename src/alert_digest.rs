@@ -0,0 +1,130 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveTime, TimeZone, Weekday};
+
+use std::fmt;
+
+// When to send a digest (summary) of the prices of the watched coins, i.e. "daily 09:00" or "weekly mon 09:00"
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DigestSchedule {
+    // None for daily digests
+    pub weekday:        Option<Weekday>,
+    pub time_of_day:    NaiveTime,
+}
+
+impl DigestSchedule {
+    pub fn parse(str_val: &str) -> Result<DigestSchedule, String> {
+        let tokens: Vec<&str> = str_val.split_whitespace().collect();
+
+        let (weekday, time_str) = match tokens.as_slice() {
+            ["daily", time_str] => (None, *time_str),
+            ["weekly", day_str, time_str] => {
+                let weekday = day_str.parse::<Weekday>().map_err(|_| format!("invalid day of the week: '{}'", day_str))?;
+                (Some(weekday), *time_str)
+            },
+            _ => return Err(format!("invalid digest schedule: '{}', it should be 'daily HH:MM' or 'weekly <day> HH:MM'", str_val)),
+        };
+
+        let time_of_day = NaiveTime::parse_from_str(time_str, "%H:%M").map_err(|_| format!("invalid time of day: '{}'", time_str))?;
+
+        return Ok(DigestSchedule { weekday, time_of_day });
+    }
+
+    // the next time the digest should be sent strictly after the given time, in the timezone, or local time if that's None
+    pub fn next_time_after(&self, time: DateTime<Local>, timezone: Option<FixedOffset>) -> DateTime<Local> {
+        let local_time = match timezone {
+            Some(offset) => time.with_timezone(&offset).naive_local(),
+            None =>         time.naive_local(),
+        };
+
+        // weekly ones can be up to a week away (plus a day if it's later in the day on the same day of the week)
+        let mut date = local_time.date();
+        for _ in 0..9 {
+            let candidate = date.and_time(self.time_of_day);
+            if candidate > local_time && self.weekday.map(|weekday| date.weekday() == weekday).unwrap_or(true) {
+                // Note: times which don't exist in the local timezone due to DST changes are skipped
+                let candidate_time = match timezone {
+                    Some(offset) => offset.from_local_datetime(&candidate).single().map(|t| t.with_timezone(&Local)),
+                    None =>         Local.from_local_datetime(&candidate).earliest(),
+                };
+                if let Some(candidate_time) = candidate_time {
+                    return candidate_time;
+                }
+            }
+            date = date.succ_opt().unwrap();
+        }
+
+        return time + Duration::days(1);
+    }
+
+    pub fn get_subject(&self) -> String {
+        return format!("Cryptmon {} Summary", if self.weekday.is_some() { "Weekly" } else { "Daily" });
+    }
+}
+
+impl fmt::Display for DigestSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.weekday {
+            Some(weekday) => write!(f, "weekly {} {}", weekday, self.time_of_day.format("%H:%M")),
+            None =>          write!(f, "daily {}", self.time_of_day.format("%H:%M")),
+        }
+    }
+}
+
+// parses "<schedule>, <actions>", i.e. "daily 09:00, [mailSMTP, textbelt]", returning the schedule and the actions string
+pub fn parse_digest_config(str_val: &str) -> Result<(DigestSchedule, String), String> {
+    let (schedule_str, actions_str) = str_val.split_once(',')
+                                             .ok_or_else(|| "expected the schedule, then a ',' and the actions to send it with".to_string())?;
+
+    let schedule = DigestSchedule::parse(schedule_str.trim())?;
+
+    let actions_str = actions_str.trim();
+    if actions_str.is_empty() {
+        return Err("missing actions to send the digest with".to_string());
+    }
+
+    return Ok((schedule, actions_str.to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_digest_schedule_01() {
+        // 2024-03-04 is a Monday
+        let make_time = |d: u32, h: u32, m: u32| Local.from_local_datetime(&NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+                                                                            .and_hms_opt(h, m, 0).unwrap()).unwrap();
+
+        let (schedule, actions) = parse_digest_config("daily 09:00, [mailSMTP, textbelt]").unwrap();
+        assert_eq!(actions, "[mailSMTP, textbelt]");
+        assert_eq!(schedule.next_time_after(make_time(4, 8, 59), None), make_time(4, 9, 0));
+        assert_eq!(schedule.next_time_after(make_time(4, 9, 0), None), make_time(5, 9, 0));
+
+        let (schedule, _) = parse_digest_config("weekly fri 17:30, print").unwrap();
+        assert_eq!(schedule.to_string(), "weekly Fri 17:30");
+        assert_eq!(schedule.get_subject(), "Cryptmon Weekly Summary");
+        assert_eq!(schedule.next_time_after(make_time(4, 9, 0), None), make_time(8, 17, 30));
+        assert_eq!(schedule.next_time_after(make_time(8, 18, 0), None), make_time(15, 17, 30));
+
+        assert!(parse_digest_config("daily 9am, print").is_err());
+        assert!(parse_digest_config("weekly someday 09:00, print").is_err());
+        assert!(parse_digest_config("daily 09:00").is_err());
+    }
+}
@@ -0,0 +1,700 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+// The conditions alerts trigger on, and the parser for the alert rule expression language, i.e.:
+//
+//   when BTC.price < 40000 and (ETH.change24h < -10% or ETH.volume > 1000000) then notify(pushsafer)
+//
+// Grammar:
+//   rule       := 'when' or_expr 'then' actions
+//   or_expr    := and_expr ('or' and_expr)*
+//   and_expr   := primary ('and' primary)*
+//   primary    := '(' or_expr ')' | comparison
+//   comparison := operand ('<' | '<=' | '>' | '>=') NUMBER ['%']
+//   operand    := SYMBOL '.' FIELD | SYMBOL '/' SYMBOL '.' PAIR_FIELD
+//   actions    := action_chain | '[' action_chain (',' action_chain)* ']'
+//   action_chain := action ('|' action)*
+//   action     := 'print' | 'showNotification' | 'notify' '(' PROVIDER ')' | 'runCommand:' COMMAND | PROVIDER
+//
+// where COMMAND is the rest of the rule text (or up to the next ',', '|' or ']' within a list), i.e. the path
+// to the command and any arguments. All actions in a list are run, and actions separated by '|' are fallbacks
+// to try in order if the previous one fails.
+//
+// Keywords are case-insensitive.
+
+use crate::config::{convert_time_period_string_to_seconds};
+use crate::formatting_helpers::{format_time_period};
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AlertTriggerType {
+    PriceLessThan,
+    PriceLessThanEqualTo,
+    PriceGreaterThanEqualTo,
+    PriceGreaterThan,
+}
+
+// the value of the coin an alert's trigger comparison is made against
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AlertValueType {
+    Price,
+    PercentChange1h,
+    PercentChange24h,
+    // the largest percentage move in either direction within the window (in seconds) from our own price history
+    PercentMove(u64),
+    High24h,
+    Low24h,
+    Volume24h,
+    // the below are for pairs of coins
+    PriceRatio,
+    // the percentage difference of the first coin's price relative to the second one's
+    PercentSpread,
+}
+
+impl AlertValueType {
+    // parses the name of a value, as used in both alert() rules and expressions, with an optional '%' suffix
+    // for the percentage ones, i.e. "change24h%"
+    pub fn from_name(name: &str) -> Option<AlertValueType> {
+        let is_percentage = name.ends_with('%');
+        let name = name.trim_end_matches('%');
+
+        let value_type = match name {
+            "price" =>      AlertValueType::Price,
+            "change1h" =>   AlertValueType::PercentChange1h,
+            "change24h" =>  AlertValueType::PercentChange24h,
+            "high24h" =>    AlertValueType::High24h,
+            "low24h" =>     AlertValueType::Low24h,
+            "volume" =>     AlertValueType::Volume24h,
+            "ratio" =>      AlertValueType::PriceRatio,
+            "spread" =>     AlertValueType::PercentSpread,
+            _   =>          {
+                // otherwise, see if it's a move over a time window, i.e. "move15m"
+                let window = name.strip_prefix("move").filter(|window| !window.is_empty())?;
+                AlertValueType::PercentMove(convert_time_period_string_to_seconds(window)?)
+            }
+        };
+
+        if is_percentage && !value_type.is_percentage() {
+            return None;
+        }
+
+        return Some(value_type);
+    }
+
+    pub fn is_percentage(&self) -> bool {
+        return matches!(self, AlertValueType::PercentChange1h | AlertValueType::PercentChange24h | AlertValueType::PercentMove(_) |
+                              AlertValueType::PercentSpread);
+    }
+
+    // whether the value is derived from a pair of coins, rather than a single one
+    pub fn is_pair(&self) -> bool {
+        return matches!(self, AlertValueType::PriceRatio | AlertValueType::PercentSpread);
+    }
+}
+
+impl fmt::Display for AlertValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AlertValueType::Price => write!(f, "price"),
+            AlertValueType::PercentChange1h => write!(f, "change1h"),
+            AlertValueType::PercentChange24h => write!(f, "change24h"),
+            AlertValueType::PercentMove(window) => write!(f, "move{}", format_time_period(window)),
+            AlertValueType::High24h => write!(f, "high24h"),
+            AlertValueType::Low24h => write!(f, "low24h"),
+            AlertValueType::Volume24h => write!(f, "volume"),
+            AlertValueType::PriceRatio => write!(f, "ratio"),
+            AlertValueType::PercentSpread => write!(f, "spread"),
+        }
+    }
+}
+
+impl fmt::Display for AlertTriggerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AlertTriggerType::PriceLessThan => write!(f, "<"),
+            AlertTriggerType::PriceLessThanEqualTo => write!(f, "<="),
+            AlertTriggerType::PriceGreaterThanEqualTo => write!(f, ">="),
+            AlertTriggerType::PriceGreaterThan => write!(f, ">"),
+        }
+    }
+}
+
+pub fn parse_trigger_type(comparison: &str) -> Option<AlertTriggerType> {
+    return match comparison {
+        "<" =>  Some(AlertTriggerType::PriceLessThan),
+        "<=" => Some(AlertTriggerType::PriceLessThanEqualTo),
+        ">" =>  Some(AlertTriggerType::PriceGreaterThan),
+        ">=" => Some(AlertTriggerType::PriceGreaterThanEqualTo),
+        _   =>  None,
+    };
+}
+
+pub fn should_alert_trigger(trigger_type: AlertTriggerType, trigger_value: f64, actual_value: f64) -> bool {
+    let alert_triggered =
+        (trigger_type == AlertTriggerType::PriceGreaterThan &&        actual_value >  trigger_value) ||
+        (trigger_type == AlertTriggerType::PriceGreaterThanEqualTo && actual_value >= trigger_value) ||
+        (trigger_type == AlertTriggerType::PriceLessThan &&           actual_value <  trigger_value) ||
+        (trigger_type == AlertTriggerType::PriceLessThanEqualTo &&    actual_value <= trigger_value);
+
+    return alert_triggered;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlertComparison {
+    // note: these are always lower-case
+    pub coin_symbol:            String,
+    // the second coin for pair value types, i.e. BTC for ETH/BTC ratios
+    pub pair_symbol:            Option<String>,
+    pub value_type:             AlertValueType,
+    pub trigger_type:           AlertTriggerType,
+    // in fiat currency for Price value types, or as a percentage for the percentage ones
+    pub trigger_value:          f64,
+}
+
+impl fmt::Display for AlertComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = if self.value_type.is_percentage() { "%" } else { "" };
+        write!(f, "{}.{} {} {}{}", self.get_operand_name(), self.value_type, self.trigger_type, self.trigger_value, percent)
+    }
+}
+
+impl AlertComparison {
+    // i.e. "BTC" or "ETH/BTC"
+    pub fn get_operand_name(&self) -> String {
+        if let Some(pair_symbol) = &self.pair_symbol {
+            return format!("{}/{}", self.coin_symbol.to_ascii_uppercase(), pair_symbol.to_ascii_uppercase());
+        }
+
+        return self.coin_symbol.to_ascii_uppercase();
+    }
+
+    pub fn get_coin_symbols(&self) -> Vec<&str> {
+        let mut symbols = vec![self.coin_symbol.as_str()];
+        if let Some(pair_symbol) = &self.pair_symbol {
+            symbols.push(pair_symbol);
+        }
+        return symbols;
+    }
+}
+
+// checks the value type is valid for either a single coin or a pair of coins
+pub fn validate_value_type_for_operand(value_type: AlertValueType, is_pair: bool) -> Result<(), String> {
+    if is_pair && !value_type.is_pair() {
+        return Err(format!("the '{}' field isn't valid for pairs of coins, only 'ratio' and 'spread' are", value_type));
+    }
+    else if !is_pair && value_type.is_pair() {
+        return Err(format!("the '{}' field is only valid for pairs of coins, i.e. ETH/BTC", value_type));
+    }
+
+    return Ok(());
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AlertExpression {
+    Comparison(AlertComparison),
+    And(Box<AlertExpression>, Box<AlertExpression>),
+    Or(Box<AlertExpression>, Box<AlertExpression>),
+}
+
+impl AlertExpression {
+    // for simple rules on just a single value, which is what watermarks and crossing alerts need
+    pub fn get_single_comparison(&self) -> Option<&AlertComparison> {
+        if let AlertExpression::Comparison(comparison) = self {
+            return Some(comparison);
+        }
+
+        return None;
+    }
+
+    pub fn get_comparisons(&self) -> Vec<&AlertComparison> {
+        let mut comparisons = Vec::new();
+        self.collect_comparisons(&mut comparisons);
+        return comparisons;
+    }
+
+    fn collect_comparisons<'a>(&'a self, comparisons: &mut Vec<&'a AlertComparison>) {
+        match self {
+            AlertExpression::Comparison(comparison) => comparisons.push(comparison),
+            AlertExpression::And(left, right) | AlertExpression::Or(left, right) => {
+                left.collect_comparisons(comparisons);
+                right.collect_comparisons(comparisons);
+            }
+        }
+    }
+
+    // get_value should return the current value for the comparison, or None if it's not available,
+    // in which case that comparison is treated as not being true.
+    pub fn evaluate<F>(&self, get_value: &F) -> bool
+    where
+        F: Fn(&AlertComparison) -> Option<f64>
+    {
+        return match self {
+            AlertExpression::Comparison(comparison) => {
+                get_value(comparison).map(|value| should_alert_trigger(comparison.trigger_type, comparison.trigger_value, value))
+                                     .unwrap_or(false)
+            },
+            AlertExpression::And(left, right) => left.evaluate(get_value) && right.evaluate(get_value),
+            AlertExpression::Or(left, right) => left.evaluate(get_value) || right.evaluate(get_value),
+        };
+    }
+}
+
+impl fmt::Display for AlertExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertExpression::Comparison(comparison) => write!(f, "{}", comparison),
+            AlertExpression::And(left, right) => write!(f, "({} and {})", left, right),
+            AlertExpression::Or(left, right) => write!(f, "({} or {})", left, right),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ParsedAlertRule {
+    pub expression:     AlertExpression,
+
+    // in the same form as the action param of alert() rules, i.e. "print", "showNotification", the provider name,
+    // or a list of them, i.e. "[showNotification, pushsafer|textbelt]"
+    pub action:         String,
+}
+
+#[derive(Clone, Debug)]
+pub struct AlertRuleParseError {
+    // 1-based column within the rule string
+    pub column:     usize,
+    pub message:    String,
+}
+
+impl fmt::Display for AlertRuleParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenType {
+    Word(String),
+    Number(f64),
+    Percent,
+    Dot,
+    Slash,
+    OpenParen,
+    CloseParen,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Pipe,
+    Comparison(AlertTriggerType),
+    End,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    token_type: TokenType,
+    // 1-based
+    column:     usize,
+}
+
+fn tokenise(rule: &str) -> Result<Vec<Token>, AlertRuleParseError> {
+    let chars: Vec<char> = rule.chars().collect();
+    let mut tokens = Vec::new();
+
+    let is_word_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    // whether we're within an action list
+    let mut in_brackets = false;
+
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let column = index + 1;
+
+        if c.is_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        let single_char_token = match c {
+            '%' => Some(TokenType::Percent),
+            '.' => Some(TokenType::Dot),
+            '/' => Some(TokenType::Slash),
+            '(' => Some(TokenType::OpenParen),
+            ')' => Some(TokenType::CloseParen),
+            '[' => Some(TokenType::OpenBracket),
+            ']' => Some(TokenType::CloseBracket),
+            ',' => Some(TokenType::Comma),
+            '|' => Some(TokenType::Pipe),
+            _   => None,
+        };
+        if let Some(token_type) = single_char_token {
+            if token_type == TokenType::OpenBracket {
+                in_brackets = true;
+            }
+            else if token_type == TokenType::CloseBracket {
+                in_brackets = false;
+            }
+            tokens.push(Token { token_type, column });
+            index += 1;
+            continue;
+        }
+
+        if c == '<' || c == '>' {
+            let mut comparison = c.to_string();
+            if index + 1 < chars.len() && chars[index + 1] == '=' {
+                comparison.push('=');
+            }
+            index += comparison.len();
+            tokens.push(Token { token_type: TokenType::Comparison(parse_trigger_type(&comparison).unwrap()), column });
+            continue;
+        }
+
+        if is_word_char(c) || c == '-' {
+            let start = index;
+            index += 1;
+            while index < chars.len() {
+                let next_c = chars[index];
+                // allow decimal points within numbers, but otherwise '.' separates symbols and fields...
+                let is_decimal_point = next_c == '.' && index + 1 < chars.len() && chars[index + 1].is_ascii_digit() &&
+                                        chars[start..index].iter().all(|d| d.is_ascii_digit() || *d == '-');
+                if !is_word_char(next_c) && !is_decimal_point {
+                    break;
+                }
+                index += 1;
+            }
+
+            let word: String = chars[start..index].iter().collect();
+            if word == "runCommand" && index < chars.len() && chars[index] == ':' {
+                // the command (and its args) can contain anything, so take the rest of the rule as-is, or
+                // up to the next item if it's within a list
+                let mut end = chars.len();
+                if in_brackets {
+                    end = chars[start..].iter().position(|c| *c == ',' || *c == '|' || *c == ']').map(|pos| start + pos).unwrap_or(end);
+                }
+                let command_word: String = chars[start..end].iter().collect();
+                tokens.push(Token { token_type: TokenType::Word(command_word.trim_end().to_string()), column });
+                index = end;
+                continue;
+            }
+
+            if let Ok(number) = word.parse::<f64>() {
+                tokens.push(Token { token_type: TokenType::Number(number), column });
+            }
+            else if c == '-' {
+                return Err(AlertRuleParseError { column, message: format!("invalid number: '{}'", word) });
+            }
+            else {
+                tokens.push(Token { token_type: TokenType::Word(word), column });
+            }
+            continue;
+        }
+
+        return Err(AlertRuleParseError { column, message: format!("unexpected character: '{}'", c) });
+    }
+
+    tokens.push(Token { token_type: TokenType::End, column: chars.len() + 1 });
+
+    return Ok(tokens);
+}
+
+struct RuleParser {
+    tokens:     Vec<Token>,
+    position:   usize,
+}
+
+impl RuleParser {
+    fn peek(&self) -> &Token {
+        return &self.tokens[self.position];
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.token_type != TokenType::End {
+            self.position += 1;
+        }
+        return token;
+    }
+
+    fn peek_is_keyword(&self, keyword: &str) -> bool {
+        if let TokenType::Word(word) = &self.peek().token_type {
+            return word.eq_ignore_ascii_case(keyword);
+        }
+        return false;
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), AlertRuleParseError> {
+        if !self.peek_is_keyword(keyword) {
+            return Err(self.error_at_next(&format!("expected '{}'", keyword)));
+        }
+        self.next();
+        return Ok(());
+    }
+
+    fn expect(&mut self, token_type: TokenType, description: &str) -> Result<Token, AlertRuleParseError> {
+        if self.peek().token_type != token_type {
+            return Err(self.error_at_next(&format!("expected {}", description)));
+        }
+        return Ok(self.next());
+    }
+
+    fn expect_word(&mut self, description: &str) -> Result<(String, usize), AlertRuleParseError> {
+        let token = self.peek().clone();
+        if let TokenType::Word(word) = token.token_type {
+            self.next();
+            return Ok((word, token.column));
+        }
+        return Err(self.error_at_next(&format!("expected {}", description)));
+    }
+
+    fn error_at_next(&self, message: &str) -> AlertRuleParseError {
+        let token = self.peek();
+        let found = match &token.token_type {
+            TokenType::Word(word) => format!("'{}'", word),
+            TokenType::Number(number) => format!("'{}'", number),
+            TokenType::Percent => "'%'".to_string(),
+            TokenType::Dot => "'.'".to_string(),
+            TokenType::Slash => "'/'".to_string(),
+            TokenType::OpenParen => "'('".to_string(),
+            TokenType::CloseParen => "')'".to_string(),
+            TokenType::OpenBracket => "'['".to_string(),
+            TokenType::CloseBracket => "']'".to_string(),
+            TokenType::Comma => "','".to_string(),
+            TokenType::Pipe => "'|'".to_string(),
+            TokenType::Comparison(trigger_type) => format!("'{}'", trigger_type),
+            TokenType::End => "end of rule".to_string(),
+        };
+        return AlertRuleParseError { column: token.column, message: format!("{}, found {}", message, found) };
+    }
+
+    fn parse_rule(&mut self) -> Result<ParsedAlertRule, AlertRuleParseError> {
+        self.expect_keyword("when")?;
+        let expression = self.parse_or()?;
+        self.expect_keyword("then")?;
+        let action = self.parse_actions()?;
+        self.expect(TokenType::End, "end of rule")?;
+
+        return Ok(ParsedAlertRule { expression, action });
+    }
+
+    fn parse_or(&mut self) -> Result<AlertExpression, AlertRuleParseError> {
+        let mut expression = self.parse_and()?;
+        while self.peek_is_keyword("or") {
+            self.next();
+            let right = self.parse_and()?;
+            expression = AlertExpression::Or(Box::new(expression), Box::new(right));
+        }
+        return Ok(expression);
+    }
+
+    fn parse_and(&mut self) -> Result<AlertExpression, AlertRuleParseError> {
+        let mut expression = self.parse_primary()?;
+        while self.peek_is_keyword("and") {
+            self.next();
+            let right = self.parse_primary()?;
+            expression = AlertExpression::And(Box::new(expression), Box::new(right));
+        }
+        return Ok(expression);
+    }
+
+    fn parse_primary(&mut self) -> Result<AlertExpression, AlertRuleParseError> {
+        if self.peek().token_type == TokenType::OpenParen {
+            self.next();
+            let expression = self.parse_or()?;
+            self.expect(TokenType::CloseParen, "')'")?;
+            return Ok(expression);
+        }
+
+        return self.parse_comparison();
+    }
+
+    fn parse_comparison(&mut self) -> Result<AlertExpression, AlertRuleParseError> {
+        let (symbol, _) = self.expect_word("coin symbol")?;
+
+        let mut pair_symbol = None;
+        if self.peek().token_type == TokenType::Slash {
+            self.next();
+            let (second_symbol, _) = self.expect_word("second coin symbol after '/'")?;
+            pair_symbol = Some(second_symbol.to_ascii_lowercase());
+        }
+
+        self.expect(TokenType::Dot, "'.' after coin symbol")?;
+        let (field, field_column) = self.expect_word("field name, i.e. 'price'")?;
+        let value_type = AlertValueType::from_name(&field);
+        if value_type.is_none() {
+            return Err(AlertRuleParseError { column: field_column,
+                            message: format!("unknown field: '{}'. It should be one of 'price', 'change1h', 'change24h', 'high24h', 'low24h', 'volume', 'move<period>', or for pairs of coins, 'ratio' or 'spread'", field) });
+        }
+        let value_type = value_type.unwrap();
+
+        if let Err(message) = validate_value_type_for_operand(value_type, pair_symbol.is_some()) {
+            return Err(AlertRuleParseError { column: field_column, message });
+        }
+
+        let trigger_type = match self.peek().token_type {
+            TokenType::Comparison(trigger_type) => trigger_type,
+            _ => return Err(self.error_at_next("expected comparison operator")),
+        };
+        self.next();
+
+        let trigger_value = match self.peek().token_type {
+            TokenType::Number(number) => number,
+            _ => return Err(self.error_at_next("expected number")),
+        };
+        self.next();
+
+        if self.peek().token_type == TokenType::Percent {
+            if !value_type.is_percentage() {
+                return Err(AlertRuleParseError { column: self.peek().column,
+                                message: format!("'%' isn't valid for the '{}' field, as it's not a percentage", field) });
+            }
+            self.next();
+        }
+
+        return Ok(AlertExpression::Comparison(AlertComparison { coin_symbol: symbol.to_ascii_lowercase(), pair_symbol, value_type,
+                                                                trigger_type, trigger_value }));
+    }
+
+    fn parse_actions(&mut self) -> Result<String, AlertRuleParseError> {
+        if self.peek().token_type != TokenType::OpenBracket {
+            return self.parse_action_chain();
+        }
+
+        self.next();
+        let mut action_chains = vec![self.parse_action_chain()?];
+        while self.peek().token_type == TokenType::Comma {
+            self.next();
+            action_chains.push(self.parse_action_chain()?);
+        }
+        self.expect(TokenType::CloseBracket, "',' or ']'")?;
+
+        return Ok(format!("[{}]", action_chains.join(", ")));
+    }
+
+    fn parse_action_chain(&mut self) -> Result<String, AlertRuleParseError> {
+        let mut actions = vec![self.parse_action()?];
+        while self.peek().token_type == TokenType::Pipe {
+            self.next();
+            actions.push(self.parse_action()?);
+        }
+
+        return Ok(actions.join("|"));
+    }
+
+    fn parse_action(&mut self) -> Result<String, AlertRuleParseError> {
+        let (action, action_column) = self.expect_word("action")?;
+
+        if self.peek().token_type != TokenType::OpenParen {
+            if action == "notify" {
+                return Err(self.error_at_next("expected '(' and provider name after 'notify'"));
+            }
+            return Ok(action);
+        }
+
+        if action != "notify" {
+            return Err(AlertRuleParseError { column: action_column, message: format!("unknown action: '{}'", action) });
+        }
+
+        self.next();
+        let (provider, _) = self.expect_word("alert provider name")?;
+        self.expect(TokenType::CloseParen, "')'")?;
+
+        return Ok(provider);
+    }
+}
+
+pub fn parse_alert_rule(rule: &str) -> Result<ParsedAlertRule, AlertRuleParseError> {
+    let tokens = tokenise(rule)?;
+    let mut parser = RuleParser { tokens, position: 0 };
+    return parser.parse_rule();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compound_rule_01() {
+        let rule = parse_alert_rule("when BTC.price < 40000 and ETH.change24h < -10% then notify(pushsafer)").unwrap();
+        assert_eq!(rule.action, "pushsafer");
+        assert_eq!(rule.expression.to_string(), "(BTC.price < 40000 and ETH.change24h < -10%)");
+        assert_eq!(rule.expression.get_comparisons().len(), 2);
+        assert!(rule.expression.get_single_comparison().is_none());
+
+        let rule = parse_alert_rule("when BTC.price < 40000 then runCommand:/opt/scripts/alert.sh -v \"a, b\" ").unwrap();
+        assert_eq!(rule.action, "runCommand:/opt/scripts/alert.sh -v \"a, b\"");
+
+        let rule = parse_alert_rule("when BTC.price < 40000 then [showNotification, notify(pushsafer) | textbelt, runCommand:/opt/alert.sh -v]").unwrap();
+        assert_eq!(rule.action, "[showNotification, pushsafer|textbelt, runCommand:/opt/alert.sh -v]");
+    }
+
+    #[test]
+    fn test_parse_precedence_01() {
+        // 'and' binds tighter than 'or', unless there are parentheses
+        let rule = parse_alert_rule("WHEN BTC.price > 1.5 or ETH.price > 2 and SOL.high24h >= 3 THEN print").unwrap();
+        assert_eq!(rule.expression.to_string(), "(BTC.price > 1.5 or (ETH.price > 2 and SOL.high24h >= 3))");
+        assert_eq!(rule.action, "print");
+
+        let rule = parse_alert_rule("when (BTC.price > 1.5 or ETH.price > 2) and SOL.volume >= 3 then showNotification").unwrap();
+        assert_eq!(rule.expression.to_string(), "((BTC.price > 1.5 or ETH.price > 2) and SOL.volume >= 3)");
+    }
+
+    #[test]
+    fn test_parse_errors_01() {
+        let err = parse_alert_rule("when BTC.prise < 40000 then print").unwrap_err();
+        assert_eq!(err.column, 10);
+
+        let err = parse_alert_rule("when BTC.price 40000 then print").unwrap_err();
+        assert_eq!(err.column, 16);
+
+        let err = parse_alert_rule("when BTC.price < 40000% then print").unwrap_err();
+        assert_eq!(err.column, 23);
+
+        let err = parse_alert_rule("when (BTC.price < 40000 then print").unwrap_err();
+        assert_eq!(err.column, 25);
+
+        let err = parse_alert_rule("when BTC.price < 40000 then notify(pushsafer").unwrap_err();
+        assert_eq!(err.column, 45);
+
+        let err = parse_alert_rule("when ETH/BTC.price < 0.05 then print").unwrap_err();
+        assert_eq!(err.column, 14);
+    }
+
+    #[test]
+    fn test_parse_pair_rule_01() {
+        let rule = parse_alert_rule("when ETH/BTC.ratio < 0.05 or USDT/USDC.spread > 1% then print").unwrap();
+        assert_eq!(rule.expression.to_string(), "(ETH/BTC.ratio < 0.05 or USDT/USDC.spread > 1%)");
+
+        let comparisons = rule.expression.get_comparisons();
+        assert_eq!(comparisons[0].get_coin_symbols(), vec!["eth", "btc"]);
+        assert_eq!(comparisons[1].value_type, AlertValueType::PercentSpread);
+    }
+
+    #[test]
+    fn test_evaluate_01() {
+        let rule = parse_alert_rule("when BTC.price < 40000 and ETH.change24h < -10% then print").unwrap();
+
+        let get_value = |btc_price: f64, eth_change: Option<f64>| {
+            move |comparison: &AlertComparison| {
+                if comparison.coin_symbol == "btc" { Some(btc_price) } else { eth_change }
+            }
+        };
+
+        assert!(rule.expression.evaluate(&get_value(39000.0, Some(-11.0))));
+        assert!(!rule.expression.evaluate(&get_value(41000.0, Some(-11.0))));
+        assert!(!rule.expression.evaluate(&get_value(39000.0, Some(-9.0))));
+        // missing values mean the comparison can't be true
+        assert!(!rule.expression.evaluate(&get_value(39000.0, None)));
+    }
+}
@@ -0,0 +1,366 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use crate::config::{Config, convert_time_period_string_to_seconds};
+use crate::cli_table_printer::{CLITablePrinter, Alignment};
+use crate::formatting_helpers::{smart_format};
+
+use serde::{Deserialize, Serialize};
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+
+use chrono::{DateTime, Local, Duration};
+
+// what happened when an alert's conditions were met
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AlertOutcome {
+    Fired,
+    // Note: these are mostly only recorded by backtesting, as the prices aren't normally fetched during the
+    //       global sleep (only when a digest or an escalation is due)
+    GlobalSleepSuppressed,
+    AlertSleepSuppressed,
+    WatermarkSuppressed,
+    QuietHoursHeld,
+    QuietHoursQueued,
+    DeliveryFailed,
+    Escalated,
+    Acknowledged,
+}
+
+const ALL_ALERT_OUTCOMES: [AlertOutcome; 9] = [AlertOutcome::Fired, AlertOutcome::GlobalSleepSuppressed, AlertOutcome::AlertSleepSuppressed,
+                                               AlertOutcome::WatermarkSuppressed, AlertOutcome::QuietHoursHeld, AlertOutcome::QuietHoursQueued,
+                                               AlertOutcome::DeliveryFailed, AlertOutcome::Escalated, AlertOutcome::Acknowledged];
+
+// Note: the names are the same as in the history file, so come from serde
+impl AlertOutcome {
+    pub fn from_name(name: &str) -> Option<AlertOutcome> {
+        return serde_json::from_value(serde_json::Value::String(name.to_string())).ok();
+    }
+}
+
+impl fmt::Display for AlertOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = serde_json::to_value(self).ok();
+        write!(f, "{}", name.as_ref().and_then(|n| n.as_str()).unwrap_or("unknown"))
+    }
+}
+
+// a single line in the alert history log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertHistoryRecord {
+    // RFC 3339
+    pub timestamp:  String,
+    pub rule:       String,
+    // uppercase, comma-separated if the rule has more than one
+    pub symbols:    String,
+    // None for compound rules
+    pub price:      Option<f64>,
+    // the name of the provider, or the action if it's not a provider
+    pub provider:   String,
+    pub outcome:    AlertOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail:     Option<String>,
+}
+
+impl AlertHistoryRecord {
+    pub fn new(time: DateTime<Local>, rule: &str, symbols: &str, price: Option<f64>, provider: &str, outcome: AlertOutcome) -> AlertHistoryRecord {
+        return AlertHistoryRecord { timestamp: time.to_rfc3339(), rule: rule.to_string(), symbols: symbols.to_string(), price,
+                                    provider: provider.to_string(), outcome, detail: None };
+    }
+
+    // for when the action used differs from the alert's, i.e. during quiet hours
+    pub fn with_provider(mut self, provider: &str) -> AlertHistoryRecord {
+        self.provider = provider.to_string();
+        return self;
+    }
+
+    pub fn with_detail(mut self, detail: &str) -> AlertHistoryRecord {
+        self.detail = Some(detail.to_string());
+        return self;
+    }
+}
+
+// Appends records to the history file (if enabled) as JSON lines, or keeps them in memory (i.e. for backtesting).
+pub struct AlertHistoryLog {
+    path:       Option<String>,
+
+    // only used if in_memory is set
+    in_memory:  bool,
+    records:    RefCell<Vec<AlertHistoryRecord>>,
+}
+
+impl AlertHistoryLog {
+    pub fn new(path: Option<String>) -> AlertHistoryLog {
+        return AlertHistoryLog { path, in_memory: false, records: RefCell::new(Vec::new()) };
+    }
+
+    pub fn new_in_memory() -> AlertHistoryLog {
+        return AlertHistoryLog { path: None, in_memory: true, records: RefCell::new(Vec::new()) };
+    }
+
+    pub fn take_records(&self) -> Vec<AlertHistoryRecord> {
+        return self.records.take();
+    }
+
+    pub fn record(&self, record: &AlertHistoryRecord) {
+        if self.in_memory {
+            self.records.borrow_mut().push(record.clone());
+            return;
+        }
+
+        if self.path.is_none() {
+            return;
+        }
+        let path = self.path.as_ref().unwrap();
+
+        let line = serde_json::to_string(record).unwrap();
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path);
+        let res = file.and_then(|mut f| writeln!(f, "{}", line));
+        if let Err(err) = res {
+            eprintln!("Error: Couldn't write to alert history file: '{}'. Error: {}", path, err);
+        }
+    }
+}
+
+// the options of 'cryptmon alerts history'
+#[derive(Clone, Debug, PartialEq)]
+struct AlertHistoryFilter {
+    outcome:    Option<AlertOutcome>,
+    // uppercase
+    symbol:     Option<String>,
+    rule:       Option<String>,
+    since_time: Option<DateTime<Local>>,
+    // the maximum number of the most recent records to show
+    limit:      usize,
+}
+
+impl AlertHistoryFilter {
+    // returns an error description for invalid args
+    fn parse_args(args: &[String], now: DateTime<Local>) -> Result<AlertHistoryFilter, String> {
+        let mut filter = AlertHistoryFilter { outcome: None, symbol: None, rule: None, since_time: None, limit: 50 };
+
+        let mut arg_index = 0;
+        while arg_index < args.len() {
+            let option = args[arg_index].as_str();
+            let value = args.get(arg_index + 1);
+            if value.is_none() {
+                return Err(format!("Missing value for: '{}'.", option));
+            }
+            let value = value.unwrap();
+
+            match option {
+                "--outcome" => {
+                    filter.outcome = AlertOutcome::from_name(value);
+                    if filter.outcome.is_none() {
+                        let names: Vec<String> = ALL_ALERT_OUTCOMES.iter().map(|o| o.to_string()).collect();
+                        return Err(format!("Unknown outcome: '{}'. It should be one of: {}", value, names.join(", ")));
+                    }
+                },
+                "--symbol" => filter.symbol = Some(value.to_ascii_uppercase()),
+                "--rule" =>   filter.rule = Some(value.to_string()),
+                "--since" => {
+                    let period = convert_time_period_string_to_seconds(value);
+                    if period.is_none() {
+                        return Err(format!("Invalid period: '{}'.", value));
+                    }
+                    filter.since_time = Some(now - Duration::seconds(period.unwrap() as i64));
+                },
+                "--limit" => {
+                    let parsed_limit = value.parse::<usize>();
+                    if parsed_limit.is_err() {
+                        return Err(format!("Invalid limit: '{}'.", value));
+                    }
+                    filter.limit = parsed_limit.unwrap();
+                },
+                _ => {
+                    return Err(format!("Unknown option: '{}'.", option));
+                }
+            }
+            arg_index += 2;
+        }
+
+        return Ok(filter);
+    }
+
+    fn matches(&self, record: &AlertHistoryRecord) -> bool {
+        if self.outcome.is_some() && self.outcome != Some(record.outcome) {
+            return false;
+        }
+        if let Some(symbol) = &self.symbol {
+            if !record.symbols.split(',').any(|s| s == symbol) {
+                return false;
+            }
+        }
+        if let Some(rule_text) = &self.rule {
+            if !record.rule.contains(rule_text.as_str()) {
+                return false;
+            }
+        }
+        if let Some(since_time) = self.since_time {
+            let record_time = DateTime::parse_from_rfc3339(&record.timestamp);
+            if record_time.map(|t| t < since_time).unwrap_or(true) {
+                return false;
+            }
+        }
+
+        return true;
+    }
+}
+
+// all the records in the history file which match the filter (ignoring the limit), or None if there's no file
+fn load_alert_history(path: &str, filter: &AlertHistoryFilter) -> Option<Vec<AlertHistoryRecord>> {
+    let file = std::fs::File::open(path).ok()?;
+
+    let mut records = Vec::new();
+    for (line_index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.unwrap_or_default();
+        if line.is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str::<AlertHistoryRecord>(&line);
+        if record.is_err() {
+            eprintln!("Warning: Ignoring invalid line {} in alert history file.", line_index + 1);
+            continue;
+        }
+        let record = record.unwrap();
+
+        if filter.matches(&record) {
+            records.push(record);
+        }
+    }
+
+    return Some(records);
+}
+
+// Lists the records in the alert history file, with optional filters:
+// cryptmon alerts history [--outcome <outcome>] [--symbol <symbol>] [--rule <text>] [--since <period>] [--limit <count>]
+pub fn run_alerts_history(config: &Config, args: &[String]) {
+    let usage = "Usage: cryptmon alerts history [--outcome <outcome>] [--symbol <symbol>] [--rule <text>] [--since <period>] [--limit <count>]";
+
+    if config.alert_config.history_file_path.is_none() {
+        eprintln!("Error: The alert history file is not enabled, see 'alerts.historyFile'.");
+        return;
+    }
+    let path = config.alert_config.history_file_path.as_ref().unwrap();
+
+    let filter = AlertHistoryFilter::parse_args(args, Local::now());
+    if let Err(err) = filter {
+        eprintln!("Error: {} {}", err, usage);
+        return;
+    }
+    let filter = filter.unwrap();
+
+    let records = load_alert_history(path, &filter);
+    if records.is_none() {
+        println!("No alert history found in: '{}'.", path);
+        return;
+    }
+    let records = records.unwrap();
+
+    if records.is_empty() {
+        println!("No matching alert history records found.");
+        return;
+    }
+
+    // only show the most recent ones
+    let limit = filter.limit;
+    let skip_count = records.len().saturating_sub(limit);
+
+    print_alert_history_table(&records[skip_count..]);
+    if skip_count > 0 {
+        println!("Showing the most recent {} of {} records, use '--limit' to show more.", limit, records.len());
+    }
+}
+
+pub fn print_alert_history_table(records: &[AlertHistoryRecord]) {
+    let mut table = CLITablePrinter::new(6);
+    table.add_titles(["Time", "Outcome", "Provider", "Price", "Rule", "Detail"]);
+    table.set_alignment_multiple(&[3usize], Alignment::Right);
+
+    for record in records {
+        let time = DateTime::parse_from_rfc3339(&record.timestamp).map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                                .unwrap_or_else(|_| record.timestamp.clone());
+        let price = record.price.map(smart_format).unwrap_or_else(|| "-".to_string());
+        table.add_row_strings(&[&time, &record.outcome.to_string(), &record.provider, &price, &record.rule,
+                                record.detail.as_deref().unwrap_or("")]);
+    }
+
+    println!("{}", table);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alert_outcome_names_01() {
+        for outcome in ALL_ALERT_OUTCOMES {
+            assert_eq!(AlertOutcome::from_name(&outcome.to_string()), Some(outcome));
+            assert_eq!(serde_json::to_string(&outcome).unwrap(), format!("\"{}\"", outcome));
+        }
+        assert_eq!(AlertOutcome::GlobalSleepSuppressed.to_string(), "globalSleepSuppressed");
+        assert_eq!(AlertOutcome::from_name("Fired"), None);
+    }
+
+    #[test]
+    fn test_alert_history_filter_01() {
+        let path = std::env::temp_dir().join(format!("cryptmon_alert_history_test_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let now = Local::now();
+        let log = AlertHistoryLog::new(Some(path.to_string()));
+        log.record(&AlertHistoryRecord::new(now - Duration::hours(3), "alert(BTC, <=, 40000, textbelt)", "BTC", Some(39000.0),
+                                            "textbelt", AlertOutcome::Fired));
+        log.record(&AlertHistoryRecord::new(now - Duration::hours(1), "alert(BTC, <=, 40000, textbelt)", "BTC", Some(38000.0),
+                                            "textbelt", AlertOutcome::DeliveryFailed).with_detail("Can't Connect"));
+        log.record(&AlertHistoryRecord::new(now, "when ETH/BTC.ratio < 0.05 then print", "ETH,BTC", None,
+                                            "print", AlertOutcome::Fired));
+
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+        let load = |filter_args: &[&str]| {
+            let filter = AlertHistoryFilter::parse_args(&args(filter_args), now).unwrap();
+            load_alert_history(path, &filter).unwrap()
+        };
+
+        // the records round-trip through the file
+        let records = load(&[]);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].price, Some(38000.0));
+        assert_eq!(records[1].outcome, AlertOutcome::DeliveryFailed);
+        assert_eq!(records[1].detail.as_deref(), Some("Can't Connect"));
+        assert_eq!(records[2].price, None);
+
+        assert_eq!(load(&["--outcome", "fired"]).len(), 2);
+        assert_eq!(load(&["--symbol", "eth"]).len(), 1);
+        assert_eq!(load(&["--symbol", "btc"]).len(), 3);
+        assert_eq!(load(&["--rule", "ratio"]).len(), 1);
+        assert_eq!(load(&["--since", "2h"]).len(), 2);
+        assert_eq!(load(&["--outcome", "fired", "--symbol", "BTC", "--since", "4h"]).len(), 2);
+
+        let filter = AlertHistoryFilter::parse_args(&args(&["--limit", "5"]), now).unwrap();
+        assert_eq!(filter.limit, 5);
+        assert!(AlertHistoryFilter::parse_args(&args(&["--outcome", "exploded"]), now).unwrap_err().contains("deliveryFailed"));
+        assert!(AlertHistoryFilter::parse_args(&args(&["--limit"]), now).is_err());
+        assert!(AlertHistoryFilter::parse_args(&args(&["--limit", "-1"]), now).is_err());
+        assert!(AlertHistoryFilter::parse_args(&args(&["--count", "1"]), now).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
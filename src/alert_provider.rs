@@ -13,20 +13,163 @@
  ---------
 */
 
+use crate::price_provider::{CoinPriceItem};
+use crate::formatting_helpers::{smart_format};
+
 use std::fmt;
 
+use chrono::{DateTime, Local};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl AlertSeverity {
+    pub fn from_name(name: &str) -> Option<AlertSeverity> {
+        return match name {
+            "info" =>     Some(AlertSeverity::Info),
+            "warning" =>  Some(AlertSeverity::Warning),
+            "critical" => Some(AlertSeverity::Critical),
+            _ =>          None,
+        };
+    }
+}
+
+impl Default for AlertSeverity {
+    fn default() -> Self {
+        return AlertSeverity::Warning;
+    }
+}
+
+impl fmt::Display for AlertSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AlertSeverity::Info =>     write!(f, "info"),
+            AlertSeverity::Warning =>  write!(f, "warning"),
+            AlertSeverity::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+// a single alert which has triggered
 #[derive(Clone, Debug)]
-pub struct AlertMessageParams {
-    pub subject:            String,
-    pub message:            String,
+pub struct TriggeredAlert {
+    // the alert's rule, as configured
+    pub rule:           String,
+    // a snapshot of the prices of all the coins in the rule when it triggered
+    pub coins:          Vec<CoinPriceItem>,
+
+    // the comparison, i.e. "price <=", the value compared against, and the current value.
+    // These are only set for rules with a single comparison.
+    pub trigger_type:   Option<String>,
+    pub threshold:      Option<f64>,
+    pub value:          Option<f64>,
+
+    // the (templated) message for the alert
+    pub message:        String,
 }
 
-impl AlertMessageParams {
-    pub fn new(subject: &str, message: &str) -> AlertMessageParams {
-        return AlertMessageParams{ subject: subject.to_string(), message: message.to_string() };
+// One or more alerts to be sent with a provider in one go. Providers can either just use the subject
+// and render_text(), or use the structured info of the alerts.
+#[derive(Clone, Debug)]
+pub struct AlertEvent {
+    pub subject:        String,
+    // optional text before the alerts, i.e. for alerts queued during quiet hours
+    pub header:         Option<String>,
+    pub alerts:         Vec<TriggeredAlert>,
+    // for scheduled digests, the coins to summarise the prices of (rendered instead of any alerts)
+    pub summary_coins:  Vec<CoinPriceItem>,
+    // the highest severity of all the alerts
+    pub severity:       AlertSeverity,
+    pub timestamp:      DateTime<Local>,
+    // uppercase
+    pub fiat_currency:  String,
+}
+
+impl AlertEvent {
+    pub fn new(subject: &str, fiat_currency: &str, timestamp: DateTime<Local>) -> AlertEvent {
+        return AlertEvent{ subject: subject.to_string(), header: None, alerts: Vec::new(), summary_coins: Vec::new(),
+                           severity: AlertSeverity::Info, timestamp,
+                           fiat_currency: fiat_currency.to_ascii_uppercase() };
+    }
+
+    pub fn add_alert(&mut self, alert: TriggeredAlert, severity: AlertSeverity) {
+        self.alerts.push(alert);
+        self.severity = self.severity.max(severity);
+    }
+
+    // the default plain text rendering: the header, then the message of each alert on separate lines,
+    // or for digests, a compact line of the coins' prices (i.e. for SMSs)
+    pub fn render_text(&self) -> String {
+        let mut lines = Vec::with_capacity(self.alerts.len() + 1);
+        if let Some(header) = &self.header {
+            lines.push(header.clone());
+        }
+        if !self.summary_coins.is_empty() {
+            lines.push(self.summary_coins.iter().map(|coin| format!("{} {} {:+.2}%", coin.symbol.to_ascii_uppercase(),
+                                                                   smart_format(coin.current_price), coin.percent_change_24h))
+                                               .collect::<Vec<String>>().join(", "));
+        }
+        lines.extend(self.alerts.iter().map(|a| a.message.clone()));
+        return lines.join("\n");
+    }
+
+    // a simple HTML rendering, with a table of the alerts and the prices of their coins, i.e. for emails,
+    // or for digests, a table of the coins' prices
+    pub fn render_html(&self) -> String {
+        let mut html = String::from("<html><body>\n");
+        if let Some(header) = &self.header {
+            html.push_str(&format!("<p>{}</p>\n", escape_html(header)));
+        }
+
+        if !self.summary_coins.is_empty() {
+            html.push_str(&format!("<table border=\"1\" cellpadding=\"4\" style=\"border-collapse: collapse\">\n<tr><th>Coin</th><th>Price ({})</th><th>24h Change</th><th>24h Low</th><th>24h High</th></tr>\n",
+                                   escape_html(&self.fiat_currency)));
+            for coin in &self.summary_coins {
+                let (low, high) = coin.watermarks_24h.as_ref().map(|w| (smart_format(w.low), smart_format(w.high))).unwrap_or_default();
+                html.push_str(&format!("<tr><td>{} ({})</td><td align=\"right\">{}</td><td align=\"right\">{:.2}%</td><td align=\"right\">{}</td><td align=\"right\">{}</td></tr>\n",
+                                       escape_html(&coin.name), escape_html(&coin.symbol.to_ascii_uppercase()),
+                                       smart_format(coin.current_price), coin.percent_change_24h, low, high));
+            }
+            html.push_str("</table>\n");
+            html.push_str(&format!("<p>{}</p>\n</body></html>\n", self.timestamp.format("%Y-%m-%d %H:%M:%S")));
+
+            return html;
+        }
+
+        html.push_str(&format!("<table border=\"1\" cellpadding=\"4\" style=\"border-collapse: collapse\">\n<tr><th>Alert</th><th>Coin</th><th>Price ({})</th><th>24h Change</th></tr>\n",
+                               escape_html(&self.fiat_currency)));
+        for alert in &self.alerts {
+            let row_count = alert.coins.len().max(1);
+            // the rule is shown under the message, so it's clear which one fired
+            html.push_str(&format!("<tr><td rowspan=\"{}\">{}<br><small>{}</small></td>", row_count,
+                                   escape_html(&alert.message).replace('\n', "<br>"), escape_html(&alert.rule)));
+            if alert.coins.is_empty() {
+                html.push_str("<td></td><td></td><td></td></tr>\n");
+            }
+            for (index, coin) in alert.coins.iter().enumerate() {
+                if index > 0 {
+                    html.push_str("<tr>");
+                }
+                html.push_str(&format!("<td>{} ({})</td><td align=\"right\">{}</td><td align=\"right\">{:.2}%</td></tr>\n",
+                                       escape_html(&coin.name), escape_html(&coin.symbol.to_ascii_uppercase()),
+                                       smart_format(coin.current_price), coin.percent_change_24h));
+            }
+        }
+        html.push_str("</table>\n");
+        html.push_str(&format!("<p>{}</p>\n</body></html>\n", self.timestamp.format("%Y-%m-%d %H:%M:%S")));
+
+        return html;
     }
 }
 
+fn escape_html(text: &str) -> String {
+    return text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;");
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum SendAlertError {
@@ -37,6 +180,8 @@ pub enum SendAlertError {
     InvalidAPIParams(String),
     ParseError(String),
     OtherError(String),
+    // the provider has run out of credits / API calls
+    QuotaExhausted(String),
     NotImplemented
 }
 
@@ -50,13 +195,64 @@ impl fmt::Display for SendAlertError {
             SendAlertError::InvalidAPIParams(ref err) => write!(f, "Invalid API params provided: {}", err),
             SendAlertError::ParseError(ref err) => write!(f, "Error parsing response: {}", err),
             SendAlertError::OtherError(ref err) => write!(f, "Error with API call: {}", err),
+            SendAlertError::QuotaExhausted(ref err) => write!(f, "Quota exhausted: {}", err),
             SendAlertError::NotImplemented => write!(f, "Not implemented"),
         }
     }
 }
 
 pub trait AlertProvider {
-    fn send_alert(&self, _message_params: AlertMessageParams) -> Result<(), SendAlertError> {
+    fn send_alert(&self, _event: &AlertEvent) -> Result<(), SendAlertError> {
         return Err(SendAlertError::NotImplemented);
     }
+
+    // the remaining quota (i.e. SMS credits) reported by the provider in response to the last alert sent, if it does so
+    fn get_quota_remaining(&self) -> Option<i64> {
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::price_provider::{Watermarks};
+
+    #[test]
+    fn test_alert_event_render_01() {
+        let coin = CoinPriceItem { price_change_24h: -1200.0, percent_change_24h: -2.95, ..CoinPriceItem::new_basic("btc", "Bitcoin", 39500.0) };
+        let alert = TriggeredAlert { rule: "alert(BTC, <=, 40000, pushsafer)".to_string(), coins: vec![coin],
+                                     trigger_type: Some("price <=".to_string()), threshold: Some(40000.0), value: Some(39500.0),
+                                     message: "BTC price <= 40000 & falling".to_string() };
+
+        let mut event = AlertEvent::new("Cryptmon Price Alert", "usd", Local::now());
+        event.add_alert(alert.clone(), AlertSeverity::Info);
+        event.add_alert(alert, AlertSeverity::Critical);
+        assert_eq!(event.severity, AlertSeverity::Critical);
+        assert_eq!(event.render_text(), "BTC price <= 40000 & falling\nBTC price <= 40000 & falling");
+
+        event.header = Some("Alerts during quiet hours:".to_string());
+        assert!(event.render_text().starts_with("Alerts during quiet hours:\nBTC"));
+
+        let html = event.render_html();
+        assert!(html.contains("<th>Price (USD)</th>"));
+        assert!(html.contains("BTC price &lt;= 40000 &amp; falling<br><small>alert(BTC, &lt;=, 40000, pushsafer)</small>"));
+        assert!(html.contains("<td>Bitcoin (BTC)</td><td align=\"right\">39,500.00</td><td align=\"right\">-2.95%</td>"));
+    }
+
+    #[test]
+    fn test_summary_event_render_01() {
+        let btc = CoinPriceItem { watermarks_24h: Some(Watermarks::new(39000.0, 41000.0)), price_change_24h: -1200.0, percent_change_24h: -2.95,
+                                  ..CoinPriceItem::new_basic("btc", "Bitcoin", 39500.0) };
+        let eth = CoinPriceItem { price_change_24h: 33.0, percent_change_24h: 1.2, ..CoinPriceItem::new_basic("eth", "Ethereum", 2800.0) };
+
+        let mut event = AlertEvent::new("Cryptmon Daily Summary", "usd", Local::now());
+        event.summary_coins = vec![btc, eth];
+        assert_eq!(event.render_text(), "BTC 39,500.00 -2.95%, ETH 2,800.00 +1.20%");
+
+        let html = event.render_html();
+        assert!(html.contains("<th>24h Low</th><th>24h High</th>"));
+        assert!(html.contains("<td align=\"right\">-2.95%</td><td align=\"right\">39,000.00</td><td align=\"right\">41,000.00</td>"));
+        assert!(!html.contains("<th>Alert</th>"));
+    }
 }
@@ -14,7 +14,7 @@
 */
 
 use crate::config::{AlertProviderConfig};
-use crate::alert_provider::{AlertProvider, SendAlertError, AlertMessageParams};
+use crate::alert_provider::{AlertProvider, SendAlertError, AlertEvent, AlertSeverity};
 
 use ureq::Error;
 
@@ -42,12 +42,20 @@ impl AlertProviderPushSafer {
 }
 
 impl AlertProvider for AlertProviderPushSafer {
-    fn send_alert(&self, message_params: AlertMessageParams) -> Result<(), SendAlertError> {
+    fn send_alert(&self, event: &AlertEvent) -> Result<(), SendAlertError> {
+
+        // map the severity to PushSafer's priority (-2 to 2)
+        let priority = match event.severity {
+            AlertSeverity::Info =>     "-1",
+            AlertSeverity::Warning =>  "0",
+            AlertSeverity::Critical => "2",
+        };
 
         let resp = ureq::post("https://www.pushsafer.com/api")
             .query("k", &self.private_key)
-            .query("t", &message_params.subject)
-            .query("m", &message_params.message)
+            .query("t", &event.subject)
+            .query("m", &event.render_text())
+            .query("pr", priority)
             .call();
 
         // TODO: there's an insane amount of boilerplate error handling and response
@@ -104,7 +112,7 @@ impl AlertProvider for AlertProviderPushSafer {
             }
             280         => {
                 eprintln!("Error: Insufficient API calls remaining... {}", resp.into_string().unwrap());
-                return Err(SendAlertError::OtherError("Insufficient API calls remaining".to_string()));
+                return Err(SendAlertError::QuotaExhausted("Insufficient API calls remaining".to_string()));
             },
             200         => {
 
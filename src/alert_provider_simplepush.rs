@@ -14,7 +14,7 @@
 */
 
 use crate::config::{AlertProviderConfig};
-use crate::alert_provider::{AlertProvider, SendAlertError, AlertMessageParams};
+use crate::alert_provider::{AlertProvider, SendAlertError, AlertEvent};
 
 use ureq::Error;
 
@@ -45,11 +45,11 @@ impl AlertProviderSimplePush {
 }
 
 impl AlertProvider for AlertProviderSimplePush {
-    fn send_alert(&self, message_params: AlertMessageParams) -> Result<(), SendAlertError> {
+    fn send_alert(&self, event: &AlertEvent) -> Result<(), SendAlertError> {
         let json_value = ureq::json!({
             "key": &self.key,
-            "title": &message_params.subject,
-            "msg": &message_params.message,
+            "title": &event.subject,
+            "msg": &event.render_text(),
             "event": "event",
         });
 
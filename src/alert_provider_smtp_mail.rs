@@ -14,7 +14,7 @@
 */
 
 use crate::config::{AlertProviderConfig};
-use crate::alert_provider::{AlertProvider, SendAlertError, AlertMessageParams};
+use crate::alert_provider::{AlertProvider, SendAlertError, AlertEvent};
 
 // Note: I found all sorts of different and apparently contradictary (in terms of use statements)
 //       examples of how to use lettre, even for just 0.9.x versions, so I don't think
@@ -62,15 +62,15 @@ impl AlertProviderSMTPMail {
 }
 
 impl AlertProvider for AlertProviderSMTPMail {
-    fn send_alert(&self, message_params: AlertMessageParams) -> Result<(), SendAlertError> {
+    fn send_alert(&self, event: &AlertEvent) -> Result<(), SendAlertError> {
 
 //        let smtp_port = 587u16;
 
         let email: lettre_email::Email = lettre_email::Email::builder()
             .to(self.to_address.to_string())
             .from(self.smtp_username.to_string())
-            .subject(message_params.subject)
-            .text(message_params.message)
+            .subject(event.subject.as_str())
+            .alternative(event.render_html(), event.render_text())
             .build().unwrap();
         
         let mut client = SmtpClient::new_simple(&self.smtp_server).unwrap()
@@ -14,10 +14,12 @@
 */
 
 use crate::config::{AlertProviderConfig};
-use crate::alert_provider::{AlertProvider, SendAlertError, AlertMessageParams};
+use crate::alert_provider::{AlertProvider, SendAlertError, AlertEvent};
 
 use ureq::Error;
 
+use std::cell::Cell;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -34,6 +36,9 @@ struct TextbeltTextResponse {
 pub struct AlertProviderTextbelt {
     pub api_key:        String,
     pub phone_number:   String,
+
+    // from the last response
+    quota_remaining:    Cell<Option<i64>>,
 }
 
 impl AlertProviderTextbelt {
@@ -48,16 +53,16 @@ impl AlertProviderTextbelt {
             eprintln!("Error: 'textbelt' Alert provider was not configured with a 'phoneNumber' param.");
             return None;
         }
-        return Some(AlertProviderTextbelt{ api_key, phone_number: phone_number.unwrap() });
+        return Some(AlertProviderTextbelt{ api_key, phone_number: phone_number.unwrap(), quota_remaining: Cell::new(None) });
     }
 }
 
 impl AlertProvider for AlertProviderTextbelt {
-    fn send_alert(&self, message_params: AlertMessageParams) -> Result<(), SendAlertError> {
+    fn send_alert(&self, event: &AlertEvent) -> Result<(), SendAlertError> {
 
         let json_value = ureq::json!({
             "phone": &self.phone_number,
-            "message": &message_params.message,
+            "message": &event.render_text(),
             "key": &self.api_key,
         });
 
@@ -103,11 +108,19 @@ impl AlertProvider for AlertProviderTextbelt {
             return Err(SendAlertError::ParseError(format!("Can't parse: {}", resp_string)));
         }
         let response_struct = response_struct.unwrap();
+        self.quota_remaining.set(Some(response_struct.quota_remaining as i64));
         if !response_struct.success {
-            return Err(SendAlertError::OtherError(response_struct.error.unwrap()));
+            let error = response_struct.error.unwrap_or_default();
+            if response_struct.quota_remaining == 0 {
+                return Err(SendAlertError::QuotaExhausted(error));
+            }
+            return Err(SendAlertError::OtherError(error));
         }
             
         return Ok(());
     }
 
+    fn get_quota_remaining(&self) -> Option<i64> {
+        return self.quota_remaining.get();
+    }
 }
@@ -0,0 +1,239 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use crate::config::{AlertConfig};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local};
+
+const USAGE_FILE_VERSION: u32 = 1;
+
+// The usage of an alert provider in a calendar month
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderUsage {
+    // i.e. "2022-08"
+    pub month:              String,
+    pub sent_count:         u32,
+    // the remaining quota reported by the provider itself, if it does so
+    #[serde(default)]
+    pub quota_remaining:    Option<i64>,
+    // the provider reported that it's run out
+    #[serde(default)]
+    pub exhausted:          bool,
+    // so we only warn once a month
+    #[serde(default)]
+    pub warned:             bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProviderUsageFile {
+    version:    u32,
+
+    // key = provider name
+    providers:  BTreeMap<String, ProviderUsage>,
+}
+
+// Tracks how many alerts have been sent with each provider per month, against either the 'monthlyLimit' provider
+// param, or the remaining quota reported by the provider (whichever is lower), persisting the counts in the usage file.
+pub struct ProviderQuotaTracker {
+    path:               Option<String>,
+
+    // key = provider name
+    monthly_limits:     BTreeMap<String, u32>,
+    // warn (and combine alerts) once the remaining quota is at or below this
+    warning_remaining:  u32,
+
+    usage:              BTreeMap<String, ProviderUsage>,
+}
+
+impl ProviderQuotaTracker {
+    pub fn new(path: Option<String>, monthly_limits: BTreeMap<String, u32>, warning_remaining: u32) -> ProviderQuotaTracker {
+        let usage = path.as_ref().map(|path| load_provider_usage(path)).unwrap_or_default();
+
+        return ProviderQuotaTracker { path, monthly_limits, warning_remaining, usage };
+    }
+
+    pub fn from_config(alert_config: &AlertConfig) -> ProviderQuotaTracker {
+        let mut monthly_limits = BTreeMap::new();
+        for (name, provider_config) in &alert_config.alert_provider_configs {
+            if let Some(limit_str) = provider_config.get_param_as_string("monthlyLimit") {
+                if let Ok(limit) = limit_str.parse::<u32>() {
+                    monthly_limits.insert(name.clone(), limit);
+                }
+                else {
+                    eprintln!("Error: Invalid 'monthlyLimit' param for alert provider: '{}': '{}'.", name, limit_str);
+                }
+            }
+        }
+
+        return ProviderQuotaTracker::new(alert_config.usage_file_path.clone(), monthly_limits, alert_config.quota_warning_remaining);
+    }
+
+    // the remaining quota for this month, if there's a limit configured or the provider has reported it
+    pub fn get_remaining(&self, provider_name: &str, time: DateTime<Local>) -> Option<i64> {
+        let usage = self.usage.get(provider_name).filter(|u| u.month == get_month(time));
+        let sent_count = usage.map(|u| u.sent_count).unwrap_or(0);
+
+        let limit_remaining = self.monthly_limits.get(provider_name).map(|limit| *limit as i64 - sent_count as i64);
+        let reported_remaining = usage.and_then(|u| u.quota_remaining);
+        return match (limit_remaining, reported_remaining) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) =>             a.or(b),
+        };
+    }
+
+    pub fn is_exhausted(&self, provider_name: &str, time: DateTime<Local>) -> bool {
+        if self.usage.get(provider_name).map(|u| u.exhausted && u.month == get_month(time)).unwrap_or(false) {
+            return true;
+        }
+        return self.get_remaining(provider_name, time).map(|r| r <= 0).unwrap_or(false);
+    }
+
+    // whether it's close to running out, in which case alerts for it should be combined
+    pub fn is_low(&self, provider_name: &str, time: DateTime<Local>) -> bool {
+        return self.get_remaining(provider_name, time).map(|r| r <= self.warning_remaining as i64).unwrap_or(false);
+    }
+
+    // whether a chain of fallback providers is close to running out of quota, which depends on the first one which
+    // hasn't run out yet, as the ones which have are skipped when sending
+    pub fn is_chain_low(&self, provider_names: &[String], time: DateTime<Local>) -> bool {
+        let provider_name = provider_names.iter().find(|name| !self.is_exhausted(name, time));
+        // if they've all run out, they'll all fail anyway
+        return provider_name.map(|name| self.is_low(name, time)).unwrap_or(true);
+    }
+
+    pub fn record_sent(&mut self, provider_name: &str, quota_remaining: Option<i64>, time: DateTime<Local>) {
+        let usage = self.get_usage_mut(provider_name, time);
+        usage.sent_count += 1;
+        if quota_remaining.is_some() {
+            usage.quota_remaining = quota_remaining;
+        }
+
+        if self.is_low(provider_name, time) {
+            let remaining = self.get_remaining(provider_name, time).unwrap();
+            let usage = self.get_usage_mut(provider_name, time);
+            if !usage.warned {
+                usage.warned = true;
+                eprintln!("Warning: Alert provider: '{}' only has {} alerts remaining in its quota, so alerts for it will be combined.",
+                          provider_name, remaining.max(0));
+            }
+        }
+
+        self.save();
+    }
+
+    pub fn record_exhausted(&mut self, provider_name: &str, time: DateTime<Local>) {
+        let usage = self.get_usage_mut(provider_name, time);
+        if !usage.exhausted {
+            eprintln!("Error: Alert provider: '{}' has run out of quota, so any fallback providers will be used instead.", provider_name);
+        }
+        usage.exhausted = true;
+        usage.quota_remaining = Some(0);
+
+        self.save();
+    }
+
+    // resets the usage if it's a new month
+    fn get_usage_mut(&mut self, provider_name: &str, time: DateTime<Local>) -> &mut ProviderUsage {
+        let month = get_month(time);
+        let usage = self.usage.entry(provider_name.to_string()).or_default();
+        if usage.month != month {
+            *usage = ProviderUsage { month, ..ProviderUsage::default() };
+        }
+        return usage;
+    }
+
+    fn save(&self) {
+        if let Some(path) = &self.path {
+            let usage_file = ProviderUsageFile { version: USAGE_FILE_VERSION, providers: self.usage.clone() };
+            let contents = serde_json::to_string_pretty(&usage_file).unwrap();
+            if let Err(err) = std::fs::write(path, contents) {
+                eprintln!("Error: Couldn't save provider usage file: '{}'. Error: {}", path, err);
+            }
+        }
+    }
+}
+
+fn get_month(time: DateTime<Local>) -> String {
+    return time.format("%Y-%m").to_string();
+}
+
+fn load_provider_usage(path: &str) -> BTreeMap<String, ProviderUsage> {
+    let contents = std::fs::read_to_string(path);
+    if contents.is_err() {
+        return BTreeMap::new();
+    }
+
+    match serde_json::from_str::<ProviderUsageFile>(&contents.unwrap()) {
+        Ok(usage_file) if usage_file.version == USAGE_FILE_VERSION => {
+            return usage_file.providers;
+        },
+        Ok(_) => {
+            eprintln!("Warning: Unsupported provider usage file version in: '{}', it will be ignored.", path);
+        },
+        Err(err) => {
+            eprintln!("Warning: Couldn't parse provider usage file: '{}', it will be ignored. Error: {}", path, err);
+        }
+    }
+
+    return BTreeMap::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{NaiveDate, TimeZone};
+
+    #[test]
+    fn test_provider_quota_01() {
+        let mut monthly_limits = BTreeMap::new();
+        monthly_limits.insert("textbelt".to_string(), 8);
+        let mut tracker = ProviderQuotaTracker::new(None, monthly_limits, 5);
+        let local_time = |month, day| Local.from_local_datetime(&NaiveDate::from_ymd_opt(2022, month, day).unwrap().and_hms_opt(12, 0, 0).unwrap()).unwrap();
+        let august = local_time(8, 20);
+        let september = local_time(9, 1);
+
+        for _ in 0..3 {
+            tracker.record_sent("textbelt", None, august);
+        }
+        assert_eq!(tracker.get_remaining("textbelt", august), Some(5));
+        assert!(tracker.is_low("textbelt", august));
+        assert!(!tracker.is_exhausted("textbelt", august));
+
+        // the provider's own count is used if it's lower
+        tracker.record_sent("textbelt", Some(0), august);
+        assert!(tracker.is_exhausted("textbelt", august));
+
+        // and it resets each month
+        assert_eq!(tracker.get_remaining("textbelt", september), Some(8));
+        assert!(!tracker.is_exhausted("textbelt", september));
+
+        assert_eq!(tracker.get_remaining("pushsafer", august), None);
+        tracker.record_exhausted("pushsafer", august);
+        assert!(tracker.is_exhausted("pushsafer", august));
+        assert!(!tracker.is_exhausted("pushsafer", september));
+
+        // chains of fallbacks depend on the first one which hasn't run out
+        let chain = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<String>>();
+        assert!(!tracker.is_chain_low(&chain(&["pushsafer", "simplepush"]), august));
+        assert!(tracker.is_chain_low(&chain(&["pushsafer", "textbelt"]), august));
+        assert!(!tracker.is_chain_low(&chain(&["textbelt", "simplepush"]), september));
+    }
+}
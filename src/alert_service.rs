@@ -13,12 +13,23 @@
  ---------
 */
 
-use crate::config::{Config, AlertConfig};
+use crate::config::{Config, AlertConfig, AlertConfigString, QuietHoursAction, convert_time_period_string_to_seconds};
 
 use crate::price_provider::{PriceProvider, PriceProviderParams, CoinPriceItem};
-use crate::formatting_helpers::{smart_format};
-
-use crate::alert_provider::{AlertProvider, AlertMessageParams};//, SendAlertError};
+use crate::price_history::{PriceHistory};
+use crate::time_window::{TimeWindow, get_time_of_day};
+use crate::alert_template::{AlertTemplate, DEFAULT_ALERT_SUBJECT, TEMPLATE_PLACEHOLDERS, render_template};
+use crate::alert_ack::{take_alert_acknowledgements, does_acknowledgement_match};
+use crate::alert_quota::{ProviderQuotaTracker};
+use crate::price_recording::{PriceRecorder, PriceRecords};
+use crate::alert_history::{AlertHistoryLog, AlertHistoryRecord, AlertOutcome};
+use crate::alert_state_file::{PersistedAlertState, get_alert_identity, load_alert_states, save_alert_states};
+use crate::alert_expression::{AlertExpression, AlertComparison, AlertTriggerType, AlertValueType, AlertRuleParseError,
+                              parse_alert_rule, parse_trigger_type, should_alert_trigger, validate_value_type_for_operand};
+use crate::alert_command::{run_alert_command};
+use crate::formatting_helpers::{smart_format, format_time_period};
+
+use crate::alert_provider::{AlertProvider, AlertEvent, AlertSeverity, TriggeredAlert, SendAlertError};
 
 #[cfg(feature = "smtp")]
 use crate::alert_provider_smtp_mail::{AlertProviderSMTPMail};
@@ -27,18 +38,28 @@ use crate::alert_provider_pushsafer::{AlertProviderPushSafer};
 use crate::alert_provider_simplepush::{AlertProviderSimplePush};
 use crate::alert_provider_textbelt::{AlertProviderTextbelt};
 
+use crate::clock::{Clock, SystemClock};
+use crate::scheduler::{FixedRateSchedule, duration_until};
+use crate::feed_health::{FeedHealthMonitor, FeedHealthChange};
+use crate::alert_digest::{DigestSchedule, parse_digest_config};
+
 use std::rc::Rc;
 
 use std::collections::BTreeMap;
 
-use chrono::{Local, Duration};
+use chrono::{DateTime, Local, Duration, TimeZone};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-enum AlertTriggerType {
-    PriceLessThan,
-    PriceLessThanEqualTo,
-    PriceGreaterThanEqualTo,
-    PriceGreaterThan,
+// the optional "key=value" params which can be given after the action in alert(...) rules
+const ALERT_KEYWORD_PARAMS: [&str; 11] = ["hysteresis", "sleep", "watermarkSleep", "combine", "activeHours", "quietHours", "template",
+                                          "severity", "escalate", "escalateAfter", "checkPeriod"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AlertTriggerMode {
+    // triggers whenever the value is past the threshold, with sleep periods suppressing repeats
+    Level,
+    // triggers only when the value moves from one side of the threshold to the other, and then only
+    // re-arms once the value has retreated past the threshold by the hysteresis amount
+    Crosses(f64),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -46,36 +67,176 @@ enum AlertAction {
     PrintMessage,
     ShowNotification,
     RunCommand(String),
-    RunProvider(String),
+    // provider names, with any after the first being fallbacks in case the previous ones fail
+    RunProvider(Vec<String>),
+}
+
+// optional per-alert overrides of the global AlertConfig settings, from "key=value" params on the alert
+#[derive(Clone, Debug, Default, PartialEq)]
+struct AlertOverrides {
+    // in seconds
+    pub sleep_period:               Option<u64>,
+
+    pub watermark_sleep_enabled:    Option<bool>,
+    // in seconds
+    pub watermark_sleep_period:     Option<u64>,
+
+    pub combine:                    Option<bool>,
+
+    // the alert is only checked within this daily time window
+    pub active_hours:               Option<TimeWindow>,
+
+    // for critical alerts which should be sent as normal during quiet hours
+    pub ignore_quiet_hours:         bool,
+
+    // the name of the alerts.template.<name> templates to use for the subject and message
+    pub template_name:              Option<String>,
+
+    pub severity:                   AlertSeverity,
+
+    // the list of actions to escalate to if the condition still holds and hasn't been acknowledged, i.e. "[pushsafer, textbelt]"
+    pub escalation_actions:         Option<String>,
+    // in seconds
+    pub escalation_period:          Option<u64>,
+
+    // how often to check the alert, in seconds
+    pub check_period:               Option<u64>,
+}
+
+impl AlertOverrides {
+    pub fn get_sleep_period(&self, alert_config: &AlertConfig) -> u64 {
+        return self.sleep_period.unwrap_or(alert_config.per_alert_sleep_period);
+    }
+
+    pub fn is_watermark_sleep_enabled(&self, alert_config: &AlertConfig) -> bool {
+        return self.watermark_sleep_enabled.unwrap_or(alert_config.watermark_trip_sleep_enabled);
+    }
+
+    pub fn get_watermark_sleep_period(&self, alert_config: &AlertConfig) -> u64 {
+        return self.watermark_sleep_period.unwrap_or(alert_config.watermark_trip_sleep_period);
+    }
+
+    pub fn get_escalation_period(&self, alert_config: &AlertConfig) -> u64 {
+        return self.escalation_period.unwrap_or(alert_config.escalation_period);
+    }
+
+    pub fn should_combine(&self, alert_config: &AlertConfig) -> bool {
+        return self.combine.unwrap_or(alert_config.combine_multiple_alerts);
+    }
+
+    // if the alert doesn't have its own, it's the shortest check period of the coins in the rule
+    pub fn get_check_period(&self, alert_config: &AlertConfig, coin_symbols: &[String]) -> u64 {
+        if let Some(check_period) = self.check_period {
+            return check_period;
+        }
+
+        return coin_symbols.iter().map(|symbol| *alert_config.coin_check_periods.get(&symbol.to_ascii_lowercase())
+                                                                .unwrap_or(&alert_config.check_period))
+                           .min().unwrap_or(alert_config.check_period);
+    }
+}
+
+impl AlertAction {
+    // the provider name(s) for providers, otherwise the action name
+    pub fn get_name(&self) -> String {
+        return match self {
+            AlertAction::PrintMessage =>        "print".to_string(),
+            AlertAction::ShowNotification =>    "showNotification".to_string(),
+            AlertAction::RunCommand(_) =>       "runCommand".to_string(),
+            AlertAction::RunProvider(names) =>  names.join("|"),
+        };
+    }
 }
 
 #[derive(Clone)]
 struct AlertItem {
-    // note: these values are always lower-case here...
-    pub coin_symbol:            String,
-    pub trigger_type:           AlertTriggerType,
-    pub trigger_price:          f64,
+    // the original rule text, for messages and errors
+    pub description:            String,
+
+    pub condition:              AlertExpression,
+    pub trigger_mode:           AlertTriggerMode,
 
-    pub action:                 AlertAction,
+    // all of these are run when the alert triggers
+    pub actions:                Vec<AlertAction>,
 
-    // this is only set for alert action types that use providers
-    pub alert_provider: Option<Rc<dyn AlertProvider>>,
+    // actions to escalate through in order if the alert isn't acknowledged, with the last one repeating
+    pub escalation_actions:     Vec<AlertAction>,
+
+    pub overrides:              AlertOverrides,
+}
+
+impl AlertItem {
+    pub fn get_actions_name(&self) -> String {
+        return self.actions.iter().map(|a| a.get_name()).collect::<Vec<String>>().join(", ");
+    }
+
+    // unique, uppercase symbols of all the coins in the rule
+    pub fn get_coin_symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = Vec::new();
+        for symbol in self.condition.get_comparisons().into_iter().flat_map(|c| c.get_coin_symbols()) {
+            let symbol = symbol.to_ascii_uppercase();
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+        return symbols;
+    }
 }
 
 //#[derive(Clone, Debug)]
 struct InternalAlertState {
     pub main_alert: AlertItem,
 
+    // stable identity for the state file
+    pub identity:   String,
+
     // additional state...
     pub last_price:     f64,
 
-    pub has_triggered:  bool,
+    // for Crosses trigger mode alerts, whether the alert can trigger. None until the first price check,
+    // as we can't know if the value has crossed until then.
+    pub crossing_armed: Option<bool>,
 
     pub previous_alert_watermark: Option<f64>,
     
     pub watermark_trip_sleep_until: Option<chrono::DateTime<Local>>,
 
     pub sleep_until:    chrono::DateTime<Local>,
+
+    // whether the alert has triggered, but we're still waiting to find out if it was sent
+    pub delivery_pending:   bool,
+
+    // the number of escalations sent so far, and when the next is due (None if it's not escalating)
+    pub escalation_level:           u32,
+    pub next_escalation_time:       Option<DateTime<Local>>,
+    // acknowledged alerts don't escalate again until their condition has cleared
+    pub escalation_acknowledged:    bool,
+
+    // in seconds, and when the alert is next due to be checked
+    pub check_period:       u64,
+    pub next_check_time:    DateTime<Local>,
+}
+
+impl InternalAlertState {
+    // called once the alert has been delivered
+    fn start_sleep_periods(&mut self, time: DateTime<Local>, watermark_value: Option<f64>, alert_config: &AlertConfig) {
+        let overrides = &self.main_alert.overrides;
+
+        let sleep_period = overrides.get_sleep_period(alert_config);
+        self.sleep_until = time.checked_add_signed(Duration::seconds(sleep_period as i64)).unwrap();
+
+        // watermarks only make sense for rules on a single value
+        if overrides.is_watermark_sleep_enabled(alert_config) && watermark_value.is_some() {
+            self.previous_alert_watermark = watermark_value;
+            let watermark_sleep_period = overrides.get_watermark_sleep_period(alert_config);
+            self.watermark_trip_sleep_until = Some(time.checked_add_signed(Duration::seconds(watermark_sleep_period as i64)).unwrap());
+        }
+
+        if !self.main_alert.escalation_actions.is_empty() && self.next_escalation_time.is_none() && !self.escalation_acknowledged {
+            self.escalation_level = 0;
+            self.next_escalation_time = Some(time + Duration::seconds(overrides.get_escalation_period(alert_config) as i64));
+        }
+    }
 }
 
 pub struct AlertService {
@@ -91,14 +252,110 @@ pub struct AlertService {
     alert_providers:    BTreeMap<String, Rc<dyn AlertProvider>>,
 
     alert_items:        Vec<InternalAlertState>,
+
+    // only records anything if there are any alerts which need it
+    price_history:      PriceHistory,
+
+    // provider alerts which triggered during quiet hours, to be sent afterwards.
+    // key = provider name
+    quiet_hours_queue:  BTreeMap<String, ProviderDelivery>,
+
+    // provider alerts which failed to send, and are waiting to be retried
+    retry_queue:        Vec<ProviderDelivery>,
+
+    history_log:        AlertHistoryLog,
+
+    quota_tracker:      ProviderQuotaTracker,
+
+    // records the fetched prices for backtesting, if enabled
+    price_recorder:     PriceRecorder,
+
+    // when the global sleep after an alert was last dispatched ends
+    next_global_sleep:  DateTime<Local>,
+
+    // for each distinct check period of the alerts, so they're checked at a fixed rate
+    check_schedules:    BTreeMap<u64, FixedRateSchedule>,
+
+    // for alerting when we can't get prices
+    feed_health:        FeedHealthMonitor,
+    feed_down_actions:  Vec<AlertAction>,
+
+    digests:            Vec<DigestItem>,
+
+    // for backtesting, where no actions are actually run, and provider alerts always succeed
+    dry_run:            bool,
+
+    clock:              Rc<dyn Clock>,
+}
+
+// a scheduled summary of the prices of the watched coins
+struct DigestItem {
+    schedule:           DigestSchedule,
+    actions:            Vec<AlertAction>,
+    next_send_time:     DateTime<Local>,
+}
+
+// an alert which has triggered, and is waiting to be sent with a provider
+struct PendingAlertDelivery {
+    // index into AlertService::alert_items
+    alert_index:        usize,
+    // the alert's value when it triggered, for the watermark trip sleep
+    watermark_value:    Option<f64>,
+    history_record:     AlertHistoryRecord,
+    // escalations don't affect the alert's sleep periods or pending state
+    is_escalation:      bool,
+}
+
+// a (possibly combined) alert event to send with a provider, which can be retried if sending it fails
+struct ProviderDelivery {
+    // the first provider to try, then any fallbacks
+    provider_names:     Vec<String>,
+    // the subject of the first alert is used if they're combined
+    event:              AlertEvent,
+    alerts:             Vec<PendingAlertDelivery>,
+
+    first_attempt_time: DateTime<Local>,
+    // the number of failed attempts so far
+    failed_attempts:    u32,
+    next_attempt_time:  DateTime<Local>,
+}
+
+impl ProviderDelivery {
+    fn new(action: &AlertAction, fiat_currency: &str, time: DateTime<Local>) -> ProviderDelivery {
+        let provider_names = if let AlertAction::RunProvider(names) = action { names.clone() } else { Vec::new() };
+        return ProviderDelivery { provider_names, event: AlertEvent::new("", fiat_currency, time), alerts: Vec::new(),
+                                  first_attempt_time: time, failed_attempts: 0, next_attempt_time: time };
+    }
+
+    fn add_alert(&mut self, subject: &str, triggered_alert: TriggeredAlert, severity: AlertSeverity, delivery: PendingAlertDelivery) {
+        if self.event.subject.is_empty() {
+            self.event.subject = subject.to_string();
+        }
+        self.event.add_alert(triggered_alert, severity);
+        self.alerts.push(delivery);
+    }
 }
 
 
 impl AlertService {
     pub fn new(config: &Config, price_provider_params: &PriceProviderParams, price_provider: Box<dyn PriceProvider>) -> Option<AlertService> {
+        return AlertService::new_with_dependencies(config, price_provider_params, price_provider, BTreeMap::new(), Rc::new(SystemClock));
+    }
+
+    // alert_providers are used in addition to the configured ones (i.e. fake ones for tests)
+    pub fn new_with_dependencies(config: &Config, price_provider_params: &PriceProviderParams, price_provider: Box<dyn PriceProvider>,
+                                 alert_providers: BTreeMap<String, Rc<dyn AlertProvider>>, clock: Rc<dyn Clock>) -> Option<AlertService> {
+        let now = clock.now();
         let mut alert_service = AlertService{ config: config.clone(), price_provider_params: price_provider_params.clone(),
-                                          price_provider, alert_providers: BTreeMap::new(),
-                                          alert_items: Vec::with_capacity(0) };
+                                          price_provider, alert_providers,
+                                          alert_items: Vec::with_capacity(0), price_history: PriceHistory::new(0),
+                                          quiet_hours_queue: BTreeMap::new(), retry_queue: Vec::new(),
+                                          history_log: AlertHistoryLog::new(config.alert_config.history_file_path.clone()),
+                                          quota_tracker: ProviderQuotaTracker::from_config(&config.alert_config),
+                                          price_recorder: PriceRecorder::new(config.alert_config.price_record_file_path.clone()),
+                                          next_global_sleep: now, check_schedules: BTreeMap::new(),
+                                          feed_health: FeedHealthMonitor::new(config.alert_config.feed_down_failures, config.alert_config.feed_down_period),
+                                          feed_down_actions: Vec::new(), digests: Vec::new(), dry_run: false, clock };
         
         // register and configure any enabled alert providers
         // TODO: not sure about the best way of doing this...
@@ -107,7 +364,7 @@ impl AlertService {
         alert_service.register_alert_provider("textbelt", &config.alert_config);
 
         #[cfg(feature = "smtp")]
-        if !alert_service.register_alert_provider("mailSMTP", &config.alert_config) {
+        if config.alert_config.alert_provider_configs.contains_key("mailSMTP") && !alert_service.register_alert_provider("mailSMTP", &config.alert_config) {
             eprintln!("Error: Support for the 'mailSMTP' provider was not compiled into this binary.");
             return None;
         }
@@ -120,26 +377,420 @@ impl AlertService {
 
         let mut wanted_coins = Vec::with_capacity(0);
 
+        let mut max_history_window = 0;
+
         for alert_item in alert_items.unwrap() {
-            wanted_coins.push(alert_item.coin_symbol.to_ascii_lowercase());
-            let internal_alert_state = InternalAlertState{ main_alert: alert_item, last_price: 0.0,
-                                                           has_triggered: false,
+            for comparison in alert_item.condition.get_comparisons() {
+                if let AlertValueType::PercentMove(window) = comparison.value_type {
+                    max_history_window = max_history_window.max(window);
+                }
+
+                for symbol in comparison.get_coin_symbols() {
+                    if !wanted_coins.iter().any(|s| s == symbol) {
+                        wanted_coins.push(symbol.to_string());
+                    }
+                }
+            }
+
+            // make sure duplicate rules have different identities
+            let mut identity = get_alert_identity(&alert_item.description);
+            let duplicate_count = alert_service.alert_items.iter().filter(|a| a.main_alert.description.split_whitespace()
+                                                                              .eq(alert_item.description.split_whitespace())).count();
+            if duplicate_count > 0 {
+                identity = format!("{} #{}", identity, duplicate_count + 1);
+            }
+
+            let check_period = alert_item.overrides.get_check_period(&config.alert_config, &alert_item.get_coin_symbols());
+            alert_service.check_schedules.entry(check_period)
+                                         .or_insert_with(|| FixedRateSchedule::new(check_period, config.alert_config.align_to_minute, now));
+
+            let internal_alert_state = InternalAlertState{ main_alert: alert_item, identity, last_price: 0.0,
+                                                           crossing_armed: None,
                                                            previous_alert_watermark: None,
                                                            watermark_trip_sleep_until: None,
-                                                           sleep_until: Local::now() };
+                                                           sleep_until: now,
+                                                           delivery_pending: false,
+                                                           escalation_level: 0,
+                                                           next_escalation_time: None,
+                                                           escalation_acknowledged: false,
+                                                           check_period,
+                                                           next_check_time: now };
 
             alert_service.alert_items.push(internal_alert_state);
         }
 
+        alert_service.price_history = PriceHistory::new(max_history_window);
+
+        if let Some(feed_down_actions) = &config.alert_config.feed_down_actions {
+            match alert_service.create_alert_actions(feed_down_actions, 1) {
+                Ok(actions) => alert_service.feed_down_actions = actions,
+                Err(err) => eprintln!("Error: invalid 'alerts.feedDownActions' value, so there will be no price feed alerts: {}", err.message),
+            }
+        }
+
+        for digest_conf in &config.alert_config.digest_config_strings {
+            let digest = parse_digest_config(&digest_conf.text)
+                            .and_then(|(schedule, actions)| alert_service.create_alert_actions(&actions, 1).map(|actions| (schedule, actions))
+                                                                                                            .map_err(|err| err.message));
+            match digest {
+                Ok((schedule, actions)) => {
+                    let next_send_time = schedule.next_time_after(now, config.alert_config.timezone);
+                    alert_service.digests.push(DigestItem { schedule, actions, next_send_time });
+                },
+                Err(err) => {
+                    eprintln!("Error: invalid digest on line {} of cryptmon.ini, it will be ignored: {}", digest_conf.line_number, err);
+                }
+            }
+        }
+
+        alert_service.restore_alert_states();
+
         // lazily update the price provider with the symbols we want by reconfiguring it again...
         // Not amazingly happy about this, but I'm less happy with alternatives in this chicken-and-egg situation...
         alert_service.price_provider_params.wanted_coin_symbols = wanted_coins;
         let mut_provider = &mut alert_service.price_provider;
-        mut_provider.configure(&alert_service.price_provider_params);
+        if let Some(config_details) = mut_provider.configure(&alert_service.price_provider_params) {
+            config_details.report_unresolved_symbols();
+
+            if !config_details.have_percent_change_1h &&
+                alert_service.alert_items.iter().any(|a| a.main_alert.condition.get_comparisons().iter()
+                                                            .any(|c| c.value_type == AlertValueType::PercentChange1h)) {
+                eprintln!("Warning: The configured price provider doesn't provide 1h percentage change values, so 'change1h' alerts will never trigger.");
+            }
+        }
 
         return Some(alert_service);
     }
 
+    // Restores the state of alerts from the state file (if enabled), so that alerts which triggered before a
+    // restart don't immediately trigger again. Alerts whose rules have changed won't match, so are reset.
+    fn restore_alert_states(&mut self) {
+        if let Some(state_file_path) = &self.config.alert_config.state_file_path {
+            let persisted_states = load_alert_states(state_file_path);
+
+            for alert in &mut self.alert_items {
+                if let Some(persisted_state) = persisted_states.get(&alert.identity) {
+                    alert.crossing_armed = persisted_state.crossing_armed;
+                    alert.previous_alert_watermark = persisted_state.previous_alert_watermark;
+                    alert.watermark_trip_sleep_until = persisted_state.watermark_trip_sleep_until.and_then(|t| Local.timestamp_opt(t, 0).single());
+                    if let Some(sleep_until) = Local.timestamp_opt(persisted_state.sleep_until, 0).single() {
+                        alert.sleep_until = sleep_until;
+                    }
+                    alert.escalation_level = persisted_state.escalation_level;
+                    alert.next_escalation_time = persisted_state.next_escalation_time.and_then(|t| Local.timestamp_opt(t, 0).single());
+                    alert.escalation_acknowledged = persisted_state.escalation_acknowledged;
+                }
+            }
+        }
+    }
+
+    fn get_alert_states_to_persist(&self) -> BTreeMap<String, PersistedAlertState> {
+        let mut alert_states = BTreeMap::new();
+        for alert in &self.alert_items {
+            let persisted_state = PersistedAlertState { crossing_armed: alert.crossing_armed,
+                                                        previous_alert_watermark: alert.previous_alert_watermark,
+                                                        watermark_trip_sleep_until: alert.watermark_trip_sleep_until.map(|t| t.timestamp()),
+                                                        sleep_until: alert.sleep_until.timestamp(),
+                                                        escalation_level: alert.escalation_level,
+                                                        next_escalation_time: alert.next_escalation_time.map(|t| t.timestamp()),
+                                                        escalation_acknowledged: alert.escalation_acknowledged };
+            alert_states.insert(alert.identity.clone(), persisted_state);
+        }
+        return alert_states;
+    }
+
+    // sends the message with the provider (or the fallback providers in order if that fails), and starts the
+    // sleep periods of the alerts if it succeeded, otherwise adds it to the retry queue (if it's not too old).
+    fn send_provider_delivery(&mut self, mut provider_delivery: ProviderDelivery, time: DateTime<Local>) {
+        let mut sent_with_provider = None;
+        let mut errors = Vec::new();
+        for provider_name in &provider_delivery.provider_names {
+            if self.dry_run {
+                sent_with_provider = Some(provider_name.clone());
+                break;
+            }
+
+            if self.quota_tracker.is_exhausted(provider_name, time) {
+                errors.push(format!("{}: quota exhausted", provider_name));
+                continue;
+            }
+
+            // Note: we check the providers exist when creating the alerts...
+            if let Some(provider) = self.alert_providers.get(provider_name) {
+                // TODO: maybe we want to try and do this asynchronously at some point, although it might
+                //       just be easier to set a pretty short connection timeout as a config option,
+                //       and providers can use that?
+                let res = provider.send_alert(&provider_delivery.event);
+                if let Err(err) = res {
+                    eprintln!("Error: Error sending alert with provider: '{}'. Full error: {}", provider_name, err);
+                    if let SendAlertError::QuotaExhausted(_) = err {
+                        self.quota_tracker.record_exhausted(provider_name, time);
+                    }
+                    errors.push(format!("{}: {}", provider_name, err));
+                    continue;
+                }
+
+                self.quota_tracker.record_sent(provider_name, provider.get_quota_remaining(), time);
+                sent_with_provider = Some(provider_name.clone());
+                break;
+            }
+        }
+
+        let alert_config = &self.config.alert_config;
+        if let Some(provider_name) = sent_with_provider {
+            let mut started_alert = false;
+            for delivery in provider_delivery.alerts {
+                if !delivery.is_escalation {
+                    let alert = &mut self.alert_items[delivery.alert_index];
+                    alert.delivery_pending = false;
+                    alert.start_sleep_periods(time, delivery.watermark_value, alert_config);
+                    started_alert = true;
+                }
+
+                let mut history_record = delivery.history_record.with_provider(&provider_name);
+                let mut details = Vec::new();
+                if provider_delivery.failed_attempts > 0 {
+                    details.push(format!("sent after {} retries", provider_delivery.failed_attempts));
+                }
+                if !errors.is_empty() {
+                    details.push(format!("fallback after errors from {}", errors.join("; ")));
+                }
+                if !details.is_empty() {
+                    history_record = history_record.with_detail(&details.join(", "));
+                }
+                self.history_log.record(&history_record);
+            }
+
+            if started_alert {
+                self.start_global_sleep(time);
+            }
+            return;
+        }
+
+        let err = errors.join("; ");
+
+        provider_delivery.failed_attempts += 1;
+        let give_up = time - provider_delivery.first_attempt_time >= Duration::seconds(alert_config.retry_max_age as i64);
+        let detail = if give_up { format!("{}, giving up after {} attempts", err, provider_delivery.failed_attempts) }
+                        else { format!("{}, will retry", err) };
+
+        for delivery in &provider_delivery.alerts {
+            // if we're giving up, allow the alert to trigger again
+            if !delivery.is_escalation {
+                self.alert_items[delivery.alert_index].delivery_pending = !give_up;
+            }
+
+            let mut history_record = delivery.history_record.clone().with_detail(&detail);
+            history_record.outcome = AlertOutcome::DeliveryFailed;
+            self.history_log.record(&history_record);
+        }
+
+        if !give_up {
+            // exponential backoff, capping the exponent so it can't overflow
+            let backoff_mult = 1i64 << (provider_delivery.failed_attempts - 1).min(16);
+            let retry_delay = Duration::seconds(alert_config.retry_initial_delay as i64 * backoff_mult);
+            provider_delivery.next_attempt_time = time + retry_delay;
+            self.retry_queue.push(provider_delivery);
+        }
+    }
+
+    // re-sends the alert with the next escalation action (or the last one again if they've all been used)
+    fn escalate_alert(&mut self, alert_index: usize, prices: &[CoinPriceItem], time: DateTime<Local>) {
+        let alert_config = &self.config.alert_config;
+        let alert = &self.alert_items[alert_index];
+        let m_alert = &alert.main_alert;
+
+        let escalation_actions = &m_alert.escalation_actions;
+        let action = escalation_actions[(alert.escalation_level as usize).min(escalation_actions.len() - 1)].clone();
+        let escalation_level = alert.escalation_level + 1;
+
+        let default_message = format!("Unacknowledged alert (escalation {}): {}", escalation_level,
+                                      build_alert_message(m_alert, prices, &self.price_history, time));
+        let template_values = build_alert_template_values(m_alert, prices, &self.price_history, time, &alert_config.fiat_currency, default_message);
+        let (subject, alert_message) = render_alert_text(alert_config, m_alert, &action, &template_values);
+
+        let history_record = build_alert_history_record(m_alert, prices, time, AlertOutcome::Escalated).with_provider(&action.get_name())
+                                .with_detail(&format!("escalation {}", escalation_level));
+
+        match &action {
+            // when backtesting, only provider alerts are handled as normal (and always succeed)
+            _ if self.dry_run && !matches!(action, AlertAction::RunProvider(_)) => {
+                self.history_log.record(&history_record);
+            },
+            AlertAction::PrintMessage => {
+                eprintln!("{}", alert_message);
+                self.history_log.record(&history_record);
+            },
+            AlertAction::ShowNotification => {
+                #[cfg(feature = "notifications")]
+                notifica::notify(&subject, &alert_message).unwrap();
+                self.history_log.record(&history_record);
+            },
+            AlertAction::RunCommand(command_line) => {
+                let env_vars = build_alert_command_env_vars(m_alert, prices, &self.price_history, time, &alert_config.fiat_currency, &alert_message);
+                if let Err(err) = run_alert_command(command_line, &env_vars, alert_config.command_timeout) {
+                    eprintln!("Error: Error running escalation command for alert: '{}'. {}", m_alert.description, err);
+                    let mut history_record = history_record.with_detail(&err);
+                    history_record.outcome = AlertOutcome::DeliveryFailed;
+                    self.history_log.record(&history_record);
+                }
+                else {
+                    self.history_log.record(&history_record);
+                }
+            },
+            AlertAction::RunProvider(_) => {
+                let triggered_alert = build_triggered_alert(m_alert, prices, &self.price_history, time, &alert_message);
+                let severity = m_alert.overrides.severity;
+                let delivery = PendingAlertDelivery { alert_index, watermark_value: None, history_record, is_escalation: true };
+                let mut provider_delivery = ProviderDelivery::new(&action, &alert_config.fiat_currency, time);
+                provider_delivery.add_alert(&subject, triggered_alert, severity, delivery);
+                self.send_provider_delivery(provider_delivery, time);
+            },
+        }
+
+        let escalation_period = self.alert_items[alert_index].main_alert.overrides.get_escalation_period(&self.config.alert_config);
+        let alert = &mut self.alert_items[alert_index];
+        alert.escalation_level = escalation_level;
+        alert.next_escalation_time = Some(time + Duration::seconds(escalation_period as i64));
+    }
+
+    // sends a notice with the 'alerts.feedDownActions' when the price feed goes down or recovers.
+    // Note: these aren't affected by the global sleep or quiet hours, as they're about cryptmon itself.
+    fn send_feed_health_alert(&mut self, change: Option<FeedHealthChange>, time: DateTime<Local>) {
+        if change.is_none() || self.feed_down_actions.is_empty() {
+            return;
+        }
+
+        let data_provider = &self.config.alert_config.data_provider;
+        let (subject, message, severity) = match change.unwrap() {
+            FeedHealthChange::WentDown { failures, since, last_error } => {
+                ("Cryptmon Price Feed Down",
+                 format!("Couldn't get prices from '{}' for {} ({} consecutive failures), so alerts aren't being checked. Last error: {}",
+                         data_provider, format_time_period((time - since).num_seconds().max(0) as u64), failures, last_error),
+                 AlertSeverity::Critical)
+            },
+            FeedHealthChange::Recovered { since } => {
+                ("Cryptmon Price Feed Recovered",
+                 format!("Prices from '{}' are being received again, after failing for {}.",
+                         data_provider, format_time_period((time - since).num_seconds().max(0) as u64)),
+                 AlertSeverity::Info)
+            },
+        };
+
+        let mut event = AlertEvent::new(subject, &self.config.alert_config.fiat_currency, time);
+        event.add_alert(TriggeredAlert { rule: "price feed".to_string(), coins: Vec::new(), trigger_type: None, threshold: None, value: None,
+                                         message }, severity);
+
+        self.send_service_event(self.feed_down_actions.clone(), event, "priceFeed", time);
+    }
+
+    // sends any digests which are due, with the latest prices of all the watched coins
+    fn send_due_digests(&mut self, prices: &[CoinPriceItem], time: DateTime<Local>) {
+        // they're not relevant to backtesting
+        if self.dry_run {
+            return;
+        }
+
+        for digest_index in 0..self.digests.len() {
+            let digest = &mut self.digests[digest_index];
+            if digest.next_send_time > time {
+                continue;
+            }
+            digest.next_send_time = digest.schedule.next_time_after(time, self.config.alert_config.timezone);
+
+            let mut event = AlertEvent::new(&digest.schedule.get_subject(), &self.config.alert_config.fiat_currency, time);
+            event.summary_coins = self.price_provider_params.wanted_coin_symbols.iter()
+                                        .filter_map(|symbol| get_coin_item_for_symbol(symbol, prices).cloned()).collect();
+
+            let actions = digest.actions.clone();
+            self.send_service_event(actions, event, "digest", time);
+        }
+    }
+
+    // sends an event which isn't from an alert rule (i.e. price feed alerts and digests) with the actions
+    // Note: there are no alert items for these, so they don't affect any sleep periods, and they aren't recorded in the history.
+    fn send_service_event(&mut self, actions: Vec<AlertAction>, event: AlertEvent, event_name: &str, time: DateTime<Local>) {
+        for action in actions {
+            match &action {
+                AlertAction::PrintMessage => {
+                    eprintln!("{}:\n{}", event.subject, event.render_text());
+                },
+                AlertAction::ShowNotification => {
+                    #[cfg(feature = "notifications")]
+                    notifica::notify(&event.subject, &event.render_text()).unwrap();
+                },
+                AlertAction::RunCommand(command_line) => {
+                    let env_vars = vec![("CRYPTMON_ALERT_RULE", event_name.to_string()),
+                                        ("CRYPTMON_ALERT_FIAT", event.fiat_currency.clone()),
+                                        ("CRYPTMON_ALERT_SEVERITY", event.severity.to_string()),
+                                        ("CRYPTMON_ALERT_MESSAGE", event.render_text())];
+                    if let Err(err) = run_alert_command(command_line, &env_vars, self.config.alert_config.command_timeout) {
+                        eprintln!("Error: Error running command for '{}' event. {}", event_name, err);
+                    }
+                },
+                AlertAction::RunProvider(_) => {
+                    let mut provider_delivery = ProviderDelivery::new(&action, &self.config.alert_config.fiat_currency, time);
+                    provider_delivery.event = event.clone();
+                    self.send_provider_delivery(provider_delivery, time);
+                },
+            }
+        }
+    }
+
+    fn is_digest_due(&self, time: DateTime<Local>) -> bool {
+        return !self.dry_run && self.digests.iter().any(|digest| digest.next_send_time <= time);
+    }
+
+    // escalations need the prices to check the alert's condition still holds
+    fn is_escalation_due(&self, time: DateTime<Local>) -> bool {
+        return self.alert_items.iter().any(|alert| alert.next_escalation_time.map(|t| t <= time).unwrap_or(false));
+    }
+
+    // sends the alerts which were queued during quiet hours, once they've ended
+    fn send_quiet_hours_queue(&mut self, time: DateTime<Local>) {
+        let time_of_day = get_time_of_day(time, self.config.alert_config.timezone);
+        if self.config.alert_config.quiet_hours.map(|w| w.contains(time_of_day)).unwrap_or(false) {
+            return;
+        }
+
+        for mut provider_delivery in std::mem::take(&mut self.quiet_hours_queue).into_values() {
+            provider_delivery.event.header = Some("Alerts during quiet hours:".to_string());
+            self.send_provider_delivery(provider_delivery, time);
+        }
+    }
+
+    // stops the escalation of any alerts which have been acknowledged with 'cryptmon alerts ack'
+    fn process_acknowledgements(&mut self, prices: &[CoinPriceItem], time: DateTime<Local>) {
+        if self.config.alert_config.ack_file_path.is_none() {
+            return;
+        }
+
+        for rule_filter in take_alert_acknowledgements(self.config.alert_config.ack_file_path.as_ref().unwrap()) {
+            for alert in &mut self.alert_items {
+                if alert.next_escalation_time.is_none() || !does_acknowledgement_match(&alert.identity, &rule_filter) {
+                    continue;
+                }
+
+                alert.next_escalation_time = None;
+                alert.escalation_acknowledged = true;
+                let history_record = build_alert_history_record(&alert.main_alert, prices, time, AlertOutcome::Acknowledged)
+                                        .with_detail(&format!("after {} escalations", alert.escalation_level));
+                self.history_log.record(&history_record);
+            }
+        }
+    }
+
+    fn process_retry_queue(&mut self, time: DateTime<Local>) {
+        for provider_delivery in std::mem::take(&mut self.retry_queue) {
+            if provider_delivery.next_attempt_time > time {
+                self.retry_queue.push(provider_delivery);
+                continue;
+            }
+
+            self.send_provider_delivery(provider_delivery, time);
+        }
+    }
+
     fn register_alert_provider(&mut self, name: &str, config: &AlertConfig) -> bool {
         // if we don't have a config for the name, we assume it's not enabled, so ignore it and
         // don't register it...
@@ -184,289 +835,812 @@ impl AlertService {
         let mut alert_items = Vec::new();
 
         for alert_conf in &alert_config.alert_config_strings {
-            let start_parenth = alert_conf.find('(');
-            let end_parenth = alert_conf.rfind(')');
-            if start_parenth.is_none() || end_parenth.is_none() {
-                continue;
-            }
-
-            let param_contents = &alert_conf[start_parenth.unwrap()+1..end_parenth.unwrap()];
-            let params: Vec<&str> = param_contents.split(',').map(|x| x.trim()).collect();
-            if params.len() != 4 {
-                continue;
+            match self.create_alert_item(alert_conf) {
+                Ok(new_alert) => {
+                    alert_items.push(new_alert);
+                },
+                Err(err) => {
+                    eprintln!("Error: invalid alert on line {}, column {} of cryptmon.ini, it will be ignored: {}",
+                                alert_conf.line_number, alert_conf.column_offset + err.column, err.message);
+                }
             }
+        }
 
-            let symbol = params[0];
-            let alert_trigger_type = match params[1] {
-                "<" =>  Some(AlertTriggerType::PriceLessThan),
-                "<=" => Some(AlertTriggerType::PriceLessThanEqualTo),
-                ">" =>  Some(AlertTriggerType::PriceGreaterThan),
-                ">=" => Some(AlertTriggerType::PriceGreaterThanEqualTo),
-                _   =>  None,
-            };
+        if alert_items.is_empty() {
+            return None;
+        }
 
-            if symbol.is_empty() || alert_trigger_type.is_none() {
-                continue;
-            }
+        return Some(alert_items);
+    }
 
-            let alert_trigger_type = alert_trigger_type.unwrap();
+    // either of the form "when <expression> then <action>" or "alert(<symbol>, <comparison>, <value>, <action>, [key=value...])"
+    fn create_alert_item(&self, alert_conf: &AlertConfigString) -> Result<AlertItem, AlertRuleParseError> {
+        let rule_text = alert_conf.text.as_str();
+
+        let condition;
+        let mut trigger_mode = AlertTriggerMode::Level;
+        let action_string;
+        let action_column;
+        let mut overrides = AlertOverrides::default();
+
+        if rule_text.get(..4).map(|s| s.eq_ignore_ascii_case("when")).unwrap_or(false) {
+            let parsed_rule = parse_alert_rule(rule_text)?;
+            condition = parsed_rule.expression;
+            action_string = parsed_rule.action;
+            // the action is normalised by the parser, so we can only point to after the 'then'
+            action_column = rule_text.to_ascii_lowercase().rfind("then").map(|pos| rule_text[..pos].chars().count() + 6).unwrap_or(1);
+        }
+        else {
+            let (comparison, mode, action, column, alert_overrides) = parse_alert_function_rule(rule_text)?;
+            condition = AlertExpression::Comparison(comparison);
+            trigger_mode = mode;
+            overrides = alert_overrides;
+            action_string = action;
+            action_column = column;
+        }
 
-            let price_value = params[2].parse::<f64>();
-            if price_value.is_err() {
-                continue;
+        // this can either be a single action (chain), or a list of them, i.e. "[showNotification, pushsafer|textbelt]"
+        let mut action_list = action_string.as_str();
+        if let Some(inner_list) = action_list.strip_prefix('[') {
+            if !inner_list.ends_with(']') {
+                return Err(AlertRuleParseError { column: action_column, message: "expected ']' at the end of the list of actions".to_string() });
             }
-            let price_value = price_value.unwrap();
+            action_list = &inner_list[..inner_list.len() - 1];
+        }
 
-            let alert_action;
-            let alert_action_string = params[3];
-            if alert_action_string == "print" {
-                alert_action = Some(AlertAction::PrintMessage);
-            }
-            else if alert_action_string == "showNotification" {
-                alert_action = Some(AlertAction::ShowNotification);
-                
-                #[cfg(not(feature = "notifications"))]
-                eprintln!("Error: Notifications support is not compiled into this binary. Please enable the feature.");
-            }
-            else if alert_action_string.starts_with("runCommand") && alert_action_string.contains(':') {
-                // TODO: - split the string
-                alert_action = Some(AlertAction::RunCommand("".to_string()));
-            }
-            else {
-                // it's likely a generic alert provider...
-                alert_action = Some(AlertAction::RunProvider(alert_action_string.to_string()));
+        if let Some(template_name) = &overrides.template_name {
+            if !self.config.alert_config.named_templates.contains_key(template_name) {
+                let column = rule_text.find("template").map(|pos| rule_text[..pos].chars().count() + 1).unwrap_or(1);
+                return Err(AlertRuleParseError { column, message: format!("can't find alert template called '{}', it should be configured with 'alerts.template.{}.message'",
+                                                                          template_name, template_name) });
             }
+        }
 
-            if alert_action.is_none() {
-                continue;
-            }
-            let alert_action = alert_action.unwrap();
+        let mut actions = Vec::new();
+        for action_chain in action_list.split(',') {
+            actions.push(self.create_alert_action(action_chain.trim(), action_column)?);
+        }
 
-            let mut alert_provider: Option<Rc<dyn AlertProvider>> = None;
-            if let AlertAction::RunProvider(provider_name) = &alert_action {
-                // it's a generic provider, so see if we have that registered (and configured)...
-                if let Some(provider) = self.alert_providers.get(provider_name) {
-                    alert_provider = Some(Rc::clone(provider));
-                }
-                else {
-                    eprintln!("Error: can't find registered and configured Alert Provider called '{}'", provider_name);
-                    continue;
-                }
+        let mut escalation_actions = Vec::new();
+        if let Some(escalation_action_list) = &overrides.escalation_actions {
+            let column = rule_text.find("escalate=").map(|pos| rule_text[..pos].chars().count() + 10).unwrap_or(1);
+            let escalation_action_list = escalation_action_list.strip_prefix('[').and_then(|l| l.strip_suffix(']')).unwrap_or(escalation_action_list);
+            for action_chain in escalation_action_list.split(',') {
+                escalation_actions.push(self.create_alert_action(action_chain.trim(), column)?);
             }
+        }
 
-            let new_alert = AlertItem{ coin_symbol: symbol.to_ascii_lowercase(), trigger_type: alert_trigger_type,
-                                       trigger_price: price_value, action: alert_action, alert_provider };
-            alert_items.push(new_alert);
+        return Ok(AlertItem{ description: rule_text.to_string(), condition, trigger_mode, actions, escalation_actions, overrides });
+    }
+
+    // a list of actions, i.e. "[print, pushsafer|textbelt]", or a single one, for things other than alert rules
+    fn create_alert_actions(&self, action_list: &str, action_column: usize) -> Result<Vec<AlertAction>, AlertRuleParseError> {
+        let action_list = action_list.strip_prefix('[').and_then(|l| l.strip_suffix(']')).unwrap_or(action_list);
+        return action_list.split(',').map(|action_chain| self.create_alert_action(action_chain.trim(), action_column)).collect();
+    }
+
+    // a single action, or a chain of alert providers separated by '|' to try in order if sending fails
+    fn create_alert_action(&self, action_chain: &str, action_column: usize) -> Result<AlertAction, AlertRuleParseError> {
+        if action_chain.is_empty() {
+            return Err(AlertRuleParseError { column: action_column, message: "missing action".to_string() });
         }
 
-        if alert_items.is_empty() {
-            return None;
+        if action_chain == "print" {
+            return Ok(AlertAction::PrintMessage);
         }
+        else if action_chain == "showNotification" {
+            #[cfg(not(feature = "notifications"))]
+            eprintln!("Error: Notifications support is not compiled into this binary. Please enable the feature.");
 
-        return Some(alert_items);
-    }
+            return Ok(AlertAction::ShowNotification);
+        }
+        else if let Some(command_line) = action_chain.strip_prefix("runCommand:") {
+            let command_line = command_line.trim();
+            if command_line.is_empty() {
+                return Err(AlertRuleParseError { column: action_column, message: "no command specified after 'runCommand:'".to_string() });
+            }
+            return Ok(AlertAction::RunCommand(command_line.to_string()));
+        }
 
-    pub fn run(&mut self) {
+        // otherwise, it's likely a generic alert provider (or a chain of them)...
+        let mut provider_names = Vec::new();
+        for provider_name in action_chain.split('|').map(|name| name.trim()) {
+            if ["print", "showNotification"].contains(&provider_name) || provider_name.starts_with("runCommand:") {
+                return Err(AlertRuleParseError { column: action_column,
+                                message: format!("only alert providers can be fallbacks with '|', found: '{}'", provider_name) });
+            }
 
-        if self.alert_items.is_empty() {
-            eprintln!("Error: No alert items found.");
-            return;
+            // see if we have that registered (and configured)...
+            if !self.alert_providers.contains_key(provider_name) {
+                return Err(AlertRuleParseError { column: action_column,
+                                message: format!("can't find registered and configured Alert Provider called '{}'", provider_name) });
+            }
+            provider_names.push(provider_name.to_string());
         }
 
-        let mut next_global_sleep = Local::now();
+        return Ok(AlertAction::RunProvider(provider_names));
+    }
 
-        // TODO: do something about check period time-drift due to latency of getting price results...
+    fn is_in_global_sleep(&self, time: DateTime<Local>) -> bool {
+        return self.config.alert_config.global_sleep_period > 0 && self.next_global_sleep > time;
+    }
 
-        loop {
-            let results = self.price_provider.get_current_prices();
+    // the global sleep only starts once an alert has actually been delivered
+    fn start_global_sleep(&mut self, time: DateTime<Local>) {
+        if self.config.alert_config.global_sleep_period > 0 {
+            self.next_global_sleep = time.checked_add_signed(Duration::seconds(self.config.alert_config.global_sleep_period as i64)).unwrap();
+        }
+    }
 
-            if let Err(err) = results {
-                eprintln!("Error getting price results: {}", err);
+    // A single step of the alert service: checks all the alerts against the prices at the given time,
+    // and runs the actions of any which trigger.
+    pub fn evaluate(&mut self, prices: Vec<CoinPriceItem>, local_time: DateTime<Local>) {
+        // record these before any sleeping below, so the history doesn't have gaps in it
+        self.price_history.add_prices(&prices, local_time);
 
-                // TODO: maybe we don't want to wait as long first time, but want a backoff of some sort for repeated errors?
-                // TODO: and maybe even 'alerts'?
-                std::thread::sleep(std::time::Duration::from_secs(self.config.alert_config.check_period));
+        self.process_acknowledgements(&prices, local_time);
 
-                continue;
-            }
+//        eprintln!("Time: {:?}", local_time);
 
-            let prices = results.unwrap();
+        let time_of_day = get_time_of_day(local_time, self.config.alert_config.timezone);
+        let in_quiet_hours = self.config.alert_config.quiet_hours.map(|w| w.contains(time_of_day)).unwrap_or(false);
 
-            let local_time = Local::now();
+        self.send_quiet_hours_queue(local_time);
 
-//            eprintln!("Time: {:?}", local_time);
+        // see if we should skip due to the global sleep...
+        // Note: we still check the alerts in this case, so that we can record which ones were suppressed.
+        //       (check_once() doesn't even fetch the prices during the global sleep, but backtesting does)
+        let in_global_sleep = self.is_in_global_sleep(local_time);
+        // whether any alerts were delivered by actions which don't need a provider, to start the global sleep
+        let mut any_delivered = false;
 
-            // see if we should skip due to the global sleep...
-            // TODO: technically, we could skip even getting the prices for this duration, but I'm not
-            //       sure it's worth being that clever...
-            if self.config.alert_config.global_sleep_period > 0 {
-                if next_global_sleep > local_time {
-                    // skip it...
-                    std::thread::sleep(std::time::Duration::from_secs(self.config.alert_config.check_period));
-                    continue;
-                }
+        // this is used for combining multiple alerts that might occur for the same provider if that config option is enabled.
+        let mut alert_provider_alerts: BTreeMap<String, ProviderDelivery> = BTreeMap::new();
+        // and these are for provider alerts which aren't being combined
+        let mut separate_deliveries: Vec<ProviderDelivery> = Vec::new();
+        // indices of alerts which are due to be escalated
+        let mut escalations: Vec<usize> = Vec::new();
+
+        let price_history = &self.price_history;
+        let history_log = &self.history_log;
+        let quota_tracker = &self.quota_tracker;
+        let check_schedules = &self.check_schedules;
 
-                // otherwise...
-                next_global_sleep = local_time.checked_add_signed(Duration::seconds(self.config.alert_config.global_sleep_period as i64)).unwrap();
+        // brute-force it for now...
+        for (alert_index, alert) in self.alert_items.iter_mut().enumerate() {
+
+            // alerts with longer check periods than others aren't checked every time
+            if alert.next_check_time > local_time {
+                continue;
             }
+            alert.next_check_time = check_schedules[&alert.check_period].next_tick_after(local_time);
 
-            // this is used for combining multiple alerts that might occur for the same provider if that config option is enabled.
-            let mut alert_provider_alerts: BTreeMap<String, String> = BTreeMap::new();
+            // it's already triggered, and is either waiting to be retried or queued for after quiet hours
+            if alert.delivery_pending {
+                continue;
+            }
 
-            // brute-force it for now...
-            for mut alert in &mut self.alert_items {
+            let m_alert = &alert.main_alert;
 
-                // check we should validate it
-                if alert.has_triggered && alert.sleep_until > local_time {
-                    // skip it this time around, as we don't want alerts until the general sleep_until time has expired
+            if let Some(active_hours) = m_alert.overrides.active_hours {
+                if !active_hours.contains(time_of_day) {
                     continue;
                 }
+            }
+
+            let missing_symbol = m_alert.condition.get_comparisons().into_iter().flat_map(|c| c.get_coin_symbols())
+                                        .find(|symbol| get_coin_item_for_symbol(symbol, &prices).is_none());
+            if let Some(symbol) = missing_symbol {
+                eprintln!("Error: Price for symbol: {} was not found", symbol);
+                continue;
+            }
+
+            let get_value = |comparison: &AlertComparison| get_alert_value(comparison, &prices, price_history, local_time);
 
-                let current_price = get_price_for_symbol(&alert.main_alert.coin_symbol, &prices);
-                if current_price.is_none() {
-                    eprintln!("Error: Price for symbol: {} was not found", alert.main_alert.coin_symbol);
+            // watermarks and crossing hysteresis only make sense for rules on a single value
+            let single_comparison = m_alert.condition.get_single_comparison();
+            let mut current_value = None;
+            if let Some(comparison) = single_comparison {
+                current_value = get_value(comparison);
+                if current_value.is_none() {
+                    // either the provider doesn't provide this value, which we warned about at startup,
+                    // or we don't have enough price history for it yet
                     continue;
                 }
+            }
 
-                let current_price = current_price.unwrap();
+            // Note: crossing alerts are only disarmed once they've actually been dispatched (below), so ones which are
+            //       suppressed by the global sleep or held during quiet hours can trigger afterwards if still past the threshold
+            let mut crossing_armed = alert.crossing_armed;
+            let alert_triggered = match (m_alert.trigger_mode, single_comparison) {
+                (AlertTriggerMode::Crosses(hysteresis), Some(comparison)) => {
+                    should_crossing_alert_trigger(&mut crossing_armed, comparison.trigger_type, comparison.trigger_value,
+                                                  hysteresis, current_value.unwrap())
+                },
+                _ => m_alert.condition.evaluate(&get_value),
+            };
+            if !alert_triggered {
+                alert.crossing_armed = crossing_armed;
+            }
+            // crossing alerts are edge-triggered, so don't need the sleep periods below to suppress repeats
+            let is_level_triggered = m_alert.trigger_mode == AlertTriggerMode::Level;
+
+            // escalation continues for as long as the condition holds (whatever the trigger mode), regardless of
+            // any sleep periods, but not during quiet hours (unless the alert ignores them)
+            if alert.next_escalation_time.is_some() || alert.escalation_acknowledged {
+                let condition_holds = if is_level_triggered { alert_triggered } else { m_alert.condition.evaluate(&get_value) };
+                if !condition_holds {
+                    alert.escalation_level = 0;
+                    alert.next_escalation_time = None;
+                    alert.escalation_acknowledged = false;
+                }
+                else if alert.next_escalation_time.map(|t| local_time >= t).unwrap_or(false) &&
+                        (!in_quiet_hours || m_alert.overrides.ignore_quiet_hours) {
+                    escalations.push(alert_index);
+                }
+            }
+            
+            if alert_triggered {
+                let mut should_show_alert = true;
 
-                let m_alert = &alert.main_alert;
-                let alert_triggered = should_alert_trigger(m_alert.trigger_type, m_alert.trigger_price, current_price);
-                
-                if alert_triggered {
-                    let mut should_show_alert = true;
+                // update the last price here, so it's done for all code paths...
+                if let Some(comparison) = single_comparison {
+                    alert.last_price = get_coin_item_for_symbol(&comparison.coin_symbol, &prices).unwrap().current_price;
+                }
 
-                    // update the last price here, so it's done for all code paths...
-                    alert.last_price = current_price;
+                let make_history_record = |outcome: AlertOutcome| build_alert_history_record(m_alert, &prices, local_time, outcome);
 
-                    // see if we should sleep due to general sleep...
-                    if local_time < alert.sleep_until {
-                        // we're still sleeping, so don't...
-                        should_show_alert = false;
-                    }
+                if in_global_sleep {
+                    history_log.record(&make_history_record(AlertOutcome::GlobalSleepSuppressed));
+                    continue;
+                }
 
-                    // otherwise, see if we should check watermark trip sleep...
-                    if should_show_alert && self.config.alert_config.watermark_trip_sleep_enabled {
-                        if let Some(watermark_sleep_until) = alert.watermark_trip_sleep_until {
-                            if local_time < watermark_sleep_until {
-                                let prev_watermark_val = alert.previous_alert_watermark.unwrap();
-
-                                // in theory we're still sleeping for the watermark trip sleep for this
-                                // alert, but if the watermark for this alert has been tripped,
-                                // we can alert.
-                                // So basically, only set should_show_alert = false if we haven't tripped the
-                                // existing watermark
-
-                                let should_watermark_trigger = should_alert_trigger_watermark(m_alert.trigger_type, prev_watermark_val, current_price);
-                                if !should_watermark_trigger {
-                                    should_show_alert = false;
-                                }
-                            }
-                            else {
-                                // otherwise, the watermark trip sleep has elapsed, and we want to "Action" the Alert...
+                // see if we should sleep due to general sleep...
+                if is_level_triggered && local_time < alert.sleep_until {
+                    // we're still sleeping, so don't...
+                    should_show_alert = false;
+                    history_log.record(&make_history_record(AlertOutcome::AlertSleepSuppressed));
+                }
+
+                // otherwise, see if we should check watermark trip sleep...
+                if should_show_alert && is_level_triggered && m_alert.overrides.is_watermark_sleep_enabled(&self.config.alert_config) {
+                    if let (Some(watermark_sleep_until), Some(comparison)) = (alert.watermark_trip_sleep_until, single_comparison) {
+                        if local_time < watermark_sleep_until {
+                            let prev_watermark_val = alert.previous_alert_watermark.unwrap();
+
+                            // in theory we're still sleeping for the watermark trip sleep for this
+                            // alert, but if the watermark for this alert has been tripped,
+                            // we can alert.
+                            // So basically, only set should_show_alert = false if we haven't tripped the
+                            // existing watermark
+
+                            // Note: the watermark is of the alert's value, so for percentage change alerts it's
+                            //       the percentage change value, i.e. a further drop of the 24h change...
+                            let should_watermark_trigger = should_alert_trigger_watermark(comparison.trigger_type, prev_watermark_val,
+                                                                                          current_value.unwrap());
+                            if !should_watermark_trigger {
+                                should_show_alert = false;
+                                history_log.record(&make_history_record(AlertOutcome::WatermarkSuppressed));
                             }
                         }
+                        else {
+                            // otherwise, the watermark trip sleep has elapsed, and we want to "Action" the Alert...
+                        }
                     }
+                }
 
-                    // TODO: think about what to do if multiple alerts trigger: combine the messages? Concat them based
-                    //       of the providers? i.e. for all alerts which triggered sharing the same provider, combine them?
-                    //       It's very likely we want to do something like this in the future for alerts where you might
-                    //       pay (i.e. SMS notifications), and wouldn't want duplicate messages for multiple coins at the
-                    //       same instant. Or similarly, where free notification plans for push notifications / SMSs
-                    //       provide a limited number of free API calls per month.
-
-                    if should_show_alert {
-                        alert.sleep_until = local_time.checked_add_signed(Duration::seconds(self.config.alert_config.per_alert_sleep_period as i64)).unwrap();
+                // TODO: think about what to do if multiple alerts trigger: combine the messages? Concat them based
+                //       of the providers? i.e. for all alerts which triggered sharing the same provider, combine them?
+                //       It's very likely we want to do something like this in the future for alerts where you might
+                //       pay (i.e. SMS notifications), and wouldn't want duplicate messages for multiple coins at the
+                //       same instant. Or similarly, where free notification plans for push notifications / SMSs
+                //       provide a limited number of free API calls per month.
+
+                if should_show_alert {
+                    let default_message = build_alert_message(m_alert, &prices, price_history, local_time);
+                    let template_values = build_alert_template_values(m_alert, &prices, price_history, local_time,
+                                                                      &self.config.alert_config.fiat_currency, default_message);
+
+                    // whether it's been delivered by any of the actions which don't need a provider
+                    let mut delivered = false;
+                    // whether any of the actions were run, sent or queued (rather than held)
+                    let mut dispatched = false;
+
+                    for action in &m_alert.actions {
+                        let make_action_history_record = |outcome: AlertOutcome| make_history_record(outcome).with_provider(&action.get_name());
+
+                        // provider alerts are handled differently during quiet hours, unless the alert overrides that
+                        let mut quiet_hours_action = None;
+                        if in_quiet_hours && !m_alert.overrides.ignore_quiet_hours && matches!(action, AlertAction::RunProvider(_)) {
+                            quiet_hours_action = Some(self.config.alert_config.quiet_hours_action);
+                        }
 
-                        if self.config.alert_config.watermark_trip_sleep_enabled {
-                            alert.previous_alert_watermark = Some(current_price);
-                            let watermark_sleep_until = local_time.checked_add_signed(Duration::seconds(self.config.alert_config.watermark_trip_sleep_period as i64)).unwrap();
-                            alert.watermark_trip_sleep_until = Some(watermark_sleep_until);
+                        // for provider alerts, the sleep periods are only started once we know they've been sent
+                        let delivery = PendingAlertDelivery { alert_index, watermark_value: current_value,
+                                                              history_record: make_action_history_record(AlertOutcome::Fired),
+                                                              is_escalation: false };
+
+                        let (subject, alert_message) = render_alert_text(&self.config.alert_config, m_alert, action, &template_values);
+                        let make_triggered_alert = || build_triggered_alert(m_alert, &prices, price_history, local_time, &alert_message);
+
+                        let mut alert_action = action;
+                        match quiet_hours_action {
+                            Some(QuietHoursAction::Hold) => {
+                                history_log.record(&make_action_history_record(AlertOutcome::QuietHoursHeld));
+                                continue;
+                            },
+                            Some(QuietHoursAction::Queue) => {
+                                history_log.record(&make_action_history_record(AlertOutcome::QuietHoursQueued));
+                                let delivery = PendingAlertDelivery { history_record: delivery.history_record.with_detail("queued during quiet hours"),
+                                                                      ..delivery };
+                                append_alert_message(&mut self.quiet_hours_queue, action, &self.config.alert_config.fiat_currency, &subject,
+                                                     make_triggered_alert(), m_alert.overrides.severity, delivery, local_time);
+                                alert.delivery_pending = true;
+                                dispatched = true;
+                                continue;
+                            },
+                            Some(QuietHoursAction::Print) =>            alert_action = &AlertAction::PrintMessage,
+                            Some(QuietHoursAction::ShowNotification) => alert_action = &AlertAction::ShowNotification,
+                            None => {},
                         }
+                        dispatched = true;
 
-                        let alert_message = format!("Coin: {} is at price: {}.", m_alert.coin_symbol.to_ascii_uppercase(),
-                                                        &smart_format(current_price));
-                        if m_alert.action == AlertAction::PrintMessage {
+                        if self.dry_run && !matches!(alert_action, AlertAction::RunProvider(_)) {
+                            // when backtesting, pretend it was delivered
+                            delivered = true;
+                            history_log.record(&make_history_record(AlertOutcome::Fired).with_provider(&alert_action.get_name()));
+                        }
+                        else if *alert_action == AlertAction::PrintMessage {
                             eprintln!("{}", alert_message);
+                            delivered = true;
+                            history_log.record(&make_history_record(AlertOutcome::Fired).with_provider("print"));
                         }
-                        else if m_alert.action == AlertAction::ShowNotification {
+                        else if *alert_action == AlertAction::ShowNotification {
                             #[cfg(feature = "notifications")]
-                            notifica::notify("Cryptmon Price Alert", &alert_message).unwrap();
+                            notifica::notify(&subject, &alert_message).unwrap();
+                            delivered = true;
+                            history_log.record(&make_history_record(AlertOutcome::Fired).with_provider("showNotification"));
+                        }
+                        else if let AlertAction::RunCommand(command_line) = alert_action {
+                            // Note: this blocks until the command has finished (or been killed after the timeout)
+                            let env_vars = build_alert_command_env_vars(m_alert, &prices, price_history, local_time,
+                                                                        &self.config.alert_config.fiat_currency, &alert_message);
+                            let res = run_alert_command(command_line, &env_vars, self.config.alert_config.command_timeout);
+                            if let Err(err) = res {
+                                eprintln!("Error: Error running command for alert: '{}'. {}", m_alert.description, err);
+                                history_log.record(&make_action_history_record(AlertOutcome::DeliveryFailed).with_detail(&err));
+                            }
+                            else {
+                                delivered = true;
+                                history_log.record(&make_action_history_record(AlertOutcome::Fired));
+                            }
                         }
-                        else if let AlertAction::RunProvider(provider_name) = &m_alert.action {
-                            if let Some(provider) = &m_alert.alert_provider {
-                                if self.config.alert_config.combine_multiple_alerts {
-                                    // if we need to attempt to combine multiple alerts to the same provider (we don't easily
-                                    // know ahead of time until this loop is done, although we could do a pre-pass), instead 
-                                    // of running send_alert() on the provider directly, cache the alert_message in the
-                                    // alert_provider_alerts BTreeMap<> above based off the provider name.
-
-                                    if let Some(existing_message) = alert_provider_alerts.get_mut(provider_name) {
-                                        // combine new alert message with existing string for this provider
-                                        let combined_message = format!("{}\n{}", existing_message, alert_message);
-                                        *existing_message = combined_message;
-                                    }
-                                    else {
-                                        // no existing alert for this provider, so just add it to the map
-                                        alert_provider_alerts.insert(provider_name.to_string(), alert_message);
-                                    }
-
-                                    // skip the rest of this loop, and the non-combining alert provider involking code below...
-                                    continue;
-                                }
-
-                                let alert_message = AlertMessageParams::new("Cryptmon Price Alert", &alert_message);
-                                // TODO: maybe we want to try and do this asynchronously at some point, although it might
-                                //       just be easier to set a pretty short connection timeout as a config option,
-                                //       and providers can use that?
-                                let res = provider.send_alert(alert_message);
-                                if let Err(err) = res {
-                                    eprintln!("Error: Error sending alert with provider: '{}'. Full error: {}", provider_name, err);
-                                    // TODO: If we weren't successful in sending the alert/notification with the provider,
-                                    // we probably want to assume it wasn't sent, and thus maybe not activate any sleep_until
-                                    // in this situation until we know we've managed to send an alert?
-                                }
+                        else if let AlertAction::RunProvider(provider_names) = alert_action {
+                            // don't check the alert again until we know whether it's been sent
+                            alert.delivery_pending = true;
+
+                            // alerts for providers which are close to running out of quota are always combined
+                            if m_alert.overrides.should_combine(&self.config.alert_config) || quota_tracker.is_chain_low(provider_names, local_time) {
+                                // if we need to attempt to combine multiple alerts to the same provider (we don't easily
+                                // know ahead of time until this loop is done, although we could do a pre-pass), instead 
+                                // of running send_alert() on the provider directly, cache the alert_message in the
+                                // alert_provider_alerts BTreeMap<> above based off the provider (chain) name.
+                                append_alert_message(&mut alert_provider_alerts, alert_action, &self.config.alert_config.fiat_currency, &subject,
+                                                     make_triggered_alert(), m_alert.overrides.severity, delivery, local_time);
+                            }
+                            else {
+                                let mut provider_delivery = ProviderDelivery::new(alert_action, &self.config.alert_config.fiat_currency, local_time);
+                                provider_delivery.add_alert(&subject, make_triggered_alert(), m_alert.overrides.severity, delivery);
+                                separate_deliveries.push(provider_delivery);
                             }
                         }
                     }
+
+                    if dispatched {
+                        alert.crossing_armed = crossing_armed;
+                    }
+
+                    if delivered {
+                        alert.start_sleep_periods(local_time, current_value, &self.config.alert_config);
+                        any_delivered = true;
+                    }
                 }
             }
+        }
 
-            // Now handle any provider alerts if we are in "combineMultipleAlerts" mode
-            // strictly-speaking, the first check should be redundant, but...
-            if self.config.alert_config.combine_multiple_alerts &&
-                !alert_provider_alerts.is_empty() {
-
-                for (provider_name, message) in alert_provider_alerts.into_iter() {
-                    if let Some(provider) = self.alert_providers.get(&provider_name) {
-                        let alert_message = AlertMessageParams::new("Cryptmon Price Alert", &message);
-                        // TODO: maybe we want to try and do this asynchronously at some point, although it might
-                        //       just be easier to set a pretty short connection timeout as a config option,
-                        //       and providers can use that?
-                        let res = provider.send_alert(alert_message);
-                        if let Err(err) = res {
-                            eprintln!("Error: Error sending alert with provider: '{}'. Full error: {}", provider_name, err);
-                            // TODO: If we weren't successful in sending the alert/notification with the provider,
-                            // we probably want to assume it wasn't sent, and thus maybe not activate any sleep_until
-                            // in this situation until we know we've managed to send an alert?
-                        }
+        if any_delivered {
+            self.start_global_sleep(local_time);
+        }
+
+        // Now send the provider alerts, with any in "combineMultipleAlerts" mode (either globally, or for
+        // individual alerts) combined per provider
+        for provider_delivery in separate_deliveries.into_iter().chain(alert_provider_alerts.into_values()) {
+            self.send_provider_delivery(provider_delivery, local_time);
+        }
+
+        for alert_index in escalations {
+            self.escalate_alert(alert_index, &prices, local_time);
+        }
+
+        self.send_due_digests(&prices, local_time);
+    }
+
+    // Replays the recorded prices through the alerts with a simulated clock (the times of the prices), without
+    // running any actions, returning the history records of what would have happened.
+    pub fn run_backtest(&mut self, price_records: PriceRecords) -> Vec<AlertHistoryRecord> {
+        if price_records.is_empty() {
+            return Vec::new();
+        }
+
+        self.dry_run = true;
+        self.history_log = AlertHistoryLog::new_in_memory();
+
+        // nothing should be sleeping at the start, and the check periods are relative to the first prices
+        let start_time = price_records[0].0;
+        self.next_global_sleep = start_time;
+        for alert in &mut self.alert_items {
+            alert.sleep_until = start_time;
+            alert.next_check_time = start_time;
+        }
+        for (check_period, schedule) in self.check_schedules.iter_mut() {
+            *schedule = FixedRateSchedule::new(*check_period, self.config.alert_config.align_to_minute, start_time);
+        }
+
+        for (time, prices) in price_records {
+            self.process_retry_queue(time);
+            self.evaluate(prices, time);
+        }
+
+        return self.history_log.take_records();
+    }
+
+    pub fn run(&mut self) {
+
+        if self.alert_items.is_empty() {
+            eprintln!("Error: No alert items found.");
+            return;
+        }
+
+        // so we only write the state file when something's changed
+        let mut persisted_alert_states = self.get_alert_states_to_persist();
+
+        loop {
+            if !self.check_once() {
+                // TODO: maybe we don't want to wait as long first time, but want a backoff of some sort for repeated errors?
+                self.sleep_until_next_check();
+
+                continue;
+            }
+
+            if let Some(state_file_path) = &self.config.alert_config.state_file_path {
+                let alert_states = self.get_alert_states_to_persist();
+                if alert_states != persisted_alert_states {
+                    if let Err(err) = save_alert_states(state_file_path, &alert_states) {
+                        eprintln!("Error: Couldn't save alert state file: '{}'. Error: {}", state_file_path, err);
                     }
+                    persisted_alert_states = alert_states;
                 }
             }
 
-            std::thread::sleep(std::time::Duration::from_secs(self.config.alert_config.check_period));
+            self.sleep_until_next_check();
+        }
+    }
+
+    // sleeps until the next tick of any of the alerts' check schedules (or the next digest), rather than for a full period
+    // after the last check finished, so the checks don't drift later by however long fetching the prices took
+    fn sleep_until_next_check(&self) {
+        let now = self.clock.now();
+        let next_check_time = self.check_schedules.values().map(|schedule| schedule.next_tick_after(now))
+                                    // any overdue digests (i.e. if the prices couldn't be fetched) are sent at the next check instead
+                                    .chain(self.digests.iter().map(|digest| digest.next_send_time).filter(|time| *time > now))
+                                    .min();
+        if let Some(next_check_time) = next_check_time {
+            self.clock.sleep(duration_until(now, next_check_time));
+        }
+    }
+
+    // fetches the latest prices and evaluates the alerts against them, returning false if the prices couldn't be fetched
+    pub fn check_once(&mut self) -> bool {
+        let now = self.clock.now();
+        self.process_retry_queue(now);
+
+        // no new alerts can be sent during the global sleep anyway, so save the API call (unless a digest or an
+        // escalation needs the prices), but still do the things which don't need them.
+        // Note: this means no price history samples or recordings are taken, crossing alerts don't re-arm,
+        //       and nothing is recorded as suppressed by the global sleep during it.
+        if self.is_in_global_sleep(now) && !self.is_digest_due(now) && !self.is_escalation_due(now) {
+            self.process_acknowledgements(&[], now);
+            self.send_quiet_hours_queue(now);
+            return true;
+        }
+
+        let results = self.price_provider.get_current_prices();
+        if let Err(err) = results {
+            eprintln!("Error getting price results: {}", err);
+            let now = self.clock.now();
+            let feed_health_change = self.feed_health.record_failure(&err.to_string(), now);
+            self.send_feed_health_alert(feed_health_change, now);
+            return false;
+        }
+        let prices = results.unwrap();
+
+        let feed_health_change = self.feed_health.record_success();
+        self.send_feed_health_alert(feed_health_change, self.clock.now());
+
+        let now = self.clock.now();
+        self.price_recorder.record(&prices, now);
+
+        self.evaluate(prices, now);
+
+        return true;
+    }
+}
+
+// parses the original "alert(<symbol>, [<value type>] [crosses] <comparison>, <value>, <action>, [key=value...])" form of
+// alert rules, returning the comparison, trigger mode, action string and the (1-based) column of the action string.
+fn parse_alert_function_rule(rule_text: &str) -> Result<(AlertComparison, AlertTriggerMode, String, usize, AlertOverrides), AlertRuleParseError> {
+    let start_parenth = rule_text.find('(');
+    let end_parenth = rule_text.rfind(')');
+    if start_parenth.is_none() || end_parenth.is_none() || end_parenth < start_parenth {
+        return Err(AlertRuleParseError { column: 1, message: "expected either 'alert(...)' or 'when ... then ...'".to_string() });
+    }
+    let start_parenth = start_parenth.unwrap();
+    let end_parenth = end_parenth.unwrap();
+
+    let get_column = |byte_index: usize| rule_text[..byte_index].chars().count() + 1;
+
+    // split up the params, keeping track of the column each one starts at for errors...
+    // Note: commas within '[...]' lists of actions don't separate params
+    let mut params: Vec<(&str, usize)> = Vec::new();
+    let mut param_start = start_parenth + 1;
+    let mut bracket_depth = 0;
+    for (index, c) in rule_text[..end_parenth + 1].char_indices().skip_while(|(index, _)| *index <= start_parenth) {
+        if c == '[' {
+            bracket_depth += 1;
+        }
+        else if c == ']' {
+            bracket_depth -= 1;
+        }
+        else if (c == ',' && bracket_depth == 0) || index == end_parenth {
+            let raw_param = &rule_text[param_start..index];
+            let leading_whitespace = raw_param.len() - raw_param.trim_start().len();
+            params.push((raw_param.trim(), get_column(param_start + leading_whitespace)));
+            param_start = index + 1;
+        }
+    }
+
+    if params.len() < 4 {
+        return Err(AlertRuleParseError { column: get_column(end_parenth),
+                        message: format!("expected at least 4 params, found {}", params.len()) });
+    }
+
+    // any params after the first four are optional "key=value" ones
+    let mut keyword_params = BTreeMap::new();
+    for (keyword_param, column) in &params[4..] {
+        if let Some((key, val)) = keyword_param.split_once('=') {
+            let key = key.trim();
+            if !ALERT_KEYWORD_PARAMS.contains(&key) {
+                return Err(AlertRuleParseError { column: *column,
+                                message: format!("unknown optional param: '{}'. It should be one of: {}", key, ALERT_KEYWORD_PARAMS.join(", ")) });
+            }
+            keyword_params.insert(key, (val.trim(), *column));
+        }
+        else {
+            return Err(AlertRuleParseError { column: *column,
+                            message: format!("invalid optional param: '{}', it should be in the form 'key=value'", keyword_param) });
+        }
+    }
+
+    let (symbol, symbol_column) = params[0];
+    if symbol.is_empty() {
+        return Err(AlertRuleParseError { column: symbol_column, message: "missing coin symbol".to_string() });
+    }
+
+    // this can be a pair of coins for ratio and spread alerts, i.e. "ETH/BTC"
+    let (symbol, pair_symbol) = match symbol.split_once('/') {
+        Some((first, second)) => (first.trim(), Some(second.trim().to_ascii_lowercase())),
+        None => (symbol, None),
+    };
+
+    // the comparison can optionally be prefixed with the value to compare against, i.e. "change24h% <=",
+    // otherwise it's the price, and/or with "crosses" to make it edge-triggered...
+    let (comparison_param, comparison_column) = params[1];
+    let comparison_tokens: Vec<&str> = comparison_param.split_whitespace().collect();
+    if comparison_tokens.is_empty() {
+        return Err(AlertRuleParseError { column: comparison_column, message: "missing comparison operator".to_string() });
+    }
+
+    let mut value_type = AlertValueType::Price;
+    let mut is_crossing = false;
+    for token in &comparison_tokens[..comparison_tokens.len() - 1] {
+        if *token == "crosses" {
+            is_crossing = true;
+            continue;
+        }
+
+        if let Some(parsed_value_type) = AlertValueType::from_name(token) {
+            value_type = parsed_value_type;
         }
+        else {
+            return Err(AlertRuleParseError { column: comparison_column,
+                            message: format!("unknown alert value type: '{}'. It should be one of 'price', 'change1h%', 'change24h%', 'high24h', 'low24h', 'volume', 'move<period>%', or for pairs of coins, 'ratio' or 'spread%'", token) });
+        }
+    }
+
+    if let Err(message) = validate_value_type_for_operand(value_type, pair_symbol.is_some()) {
+        return Err(AlertRuleParseError { column: comparison_column, message });
     }
+
+    let comparison = comparison_tokens[comparison_tokens.len() - 1];
+    let trigger_type = parse_trigger_type(comparison);
+    if trigger_type.is_none() {
+        return Err(AlertRuleParseError { column: comparison_column,
+                        message: format!("unknown comparison operator: '{}'. It should be one of '<', '<=', '>' or '>='", comparison) });
+    }
+    let trigger_type = trigger_type.unwrap();
+
+    let (trigger_value_param, trigger_value_column) = params[2];
+    let trigger_value = trigger_value_param.trim_end_matches('%').parse::<f64>();
+    if trigger_value.is_err() {
+        return Err(AlertRuleParseError { column: trigger_value_column, message: format!("invalid value: '{}'", trigger_value_param) });
+    }
+    let trigger_value = trigger_value.unwrap();
+
+    let mut trigger_mode = AlertTriggerMode::Level;
+    if is_crossing {
+        // the hysteresis band can either be an absolute value, or a percentage of the trigger value
+        let mut hysteresis = 0.0;
+        if let Some((hysteresis_str, hysteresis_column)) = keyword_params.get("hysteresis") {
+            let parsed_hysteresis = hysteresis_str.trim_end_matches('%').parse::<f64>();
+            if parsed_hysteresis.is_err() {
+                return Err(AlertRuleParseError { column: *hysteresis_column, message: format!("invalid 'hysteresis' value: '{}'", hysteresis_str) });
+            }
+            hysteresis = parsed_hysteresis.unwrap().abs();
+            if hysteresis_str.ends_with('%') {
+                hysteresis *= trigger_value.abs() / 100.0;
+            }
+        }
+        trigger_mode = AlertTriggerMode::Crosses(hysteresis);
+    }
+    else if keyword_params.contains_key("hysteresis") {
+        eprintln!("Warning: 'hysteresis' only has an effect on 'crosses' alerts, in alert: '{}'.", rule_text);
+    }
+
+    let comparison = AlertComparison { coin_symbol: symbol.to_ascii_lowercase(), pair_symbol, value_type, trigger_type, trigger_value };
+    let (action, action_column) = params[3];
+
+    let mut overrides = AlertOverrides::default();
+    if let Some((sleep_str, sleep_column)) = keyword_params.get("sleep") {
+        // "off" means alert every time it triggers
+        let sleep_period = if parse_on_off_value(sleep_str) == Some(false) { Some(0) } else { parse_time_period_value(sleep_str) };
+        if sleep_period.is_none() {
+            return Err(AlertRuleParseError { column: *sleep_column, message: format!("invalid 'sleep' value: '{}'", sleep_str) });
+        }
+        overrides.sleep_period = sleep_period;
+    }
+    if let Some((watermark_sleep_str, watermark_sleep_column)) = keyword_params.get("watermarkSleep") {
+        // either on/off, or the period, which implies on
+        if let Some(enabled) = parse_on_off_value(watermark_sleep_str) {
+            overrides.watermark_sleep_enabled = Some(enabled);
+        }
+        else if let Some(period) = parse_time_period_value(watermark_sleep_str) {
+            overrides.watermark_sleep_enabled = Some(true);
+            overrides.watermark_sleep_period = Some(period);
+        }
+        else {
+            return Err(AlertRuleParseError { column: *watermark_sleep_column,
+                            message: format!("invalid 'watermarkSleep' value: '{}', it should be 'on', 'off' or a time period", watermark_sleep_str) });
+        }
+    }
+    if let Some((combine_str, combine_column)) = keyword_params.get("combine") {
+        let combine = parse_on_off_value(combine_str);
+        if combine.is_none() {
+            return Err(AlertRuleParseError { column: *combine_column, message: format!("invalid 'combine' value: '{}', it should be 'on' or 'off'", combine_str) });
+        }
+        overrides.combine = combine;
+    }
+    if let Some((active_hours_str, active_hours_column)) = keyword_params.get("activeHours") {
+        overrides.active_hours = TimeWindow::parse(active_hours_str);
+        if overrides.active_hours.is_none() {
+            return Err(AlertRuleParseError { column: *active_hours_column,
+                            message: format!("invalid 'activeHours' value: '{}', it should be in the form '09:00-17:00'", active_hours_str) });
+        }
+    }
+    if let Some((quiet_hours_str, quiet_hours_column)) = keyword_params.get("quietHours") {
+        // 'off' means quiet hours don't apply to this alert
+        let quiet_hours = parse_on_off_value(quiet_hours_str);
+        if quiet_hours.is_none() {
+            return Err(AlertRuleParseError { column: *quiet_hours_column, message: format!("invalid 'quietHours' value: '{}', it should be 'on' or 'off'", quiet_hours_str) });
+        }
+        overrides.ignore_quiet_hours = !quiet_hours.unwrap();
+    }
+    if let Some((template_name, _)) = keyword_params.get("template") {
+        overrides.template_name = Some(template_name.to_string());
+    }
+    if let Some((severity_str, severity_column)) = keyword_params.get("severity") {
+        let severity = AlertSeverity::from_name(severity_str);
+        if severity.is_none() {
+            return Err(AlertRuleParseError { column: *severity_column, message: format!("invalid 'severity' value: '{}', it should be one of 'info', 'warning' or 'critical'", severity_str) });
+        }
+        overrides.severity = severity.unwrap();
+    }
+    if let Some((check_period_str, check_period_column)) = keyword_params.get("checkPeriod") {
+        let check_period = parse_time_period_value(check_period_str);
+        if check_period.is_none() || check_period == Some(0) {
+            return Err(AlertRuleParseError { column: *check_period_column, message: format!("invalid 'checkPeriod' value: '{}'", check_period_str) });
+        }
+        overrides.check_period = check_period;
+    }
+    if let Some((escalation_actions, _)) = keyword_params.get("escalate") {
+        overrides.escalation_actions = Some(escalation_actions.to_string());
+    }
+    if let Some((escalation_period_str, escalation_period_column)) = keyword_params.get("escalateAfter") {
+        let escalation_period = convert_time_period_string_to_seconds(escalation_period_str);
+        if escalation_period.is_none() || escalation_period == Some(0) {
+            return Err(AlertRuleParseError { column: *escalation_period_column, message: format!("invalid 'escalateAfter' value: '{}'", escalation_period_str) });
+        }
+        overrides.escalation_period = escalation_period;
+        if !keyword_params.contains_key("escalate") {
+            eprintln!("Warning: 'escalateAfter' only has an effect on alerts with 'escalate' actions, in alert: '{}'.", rule_text);
+        }
+    }
+
+    return Ok((comparison, trigger_mode, action.to_string(), action_column, overrides));
 }
 
-fn should_alert_trigger(trigger_type: AlertTriggerType, trigger_value: f64, actual_value: f64) -> bool {
-    let alert_triggered = 
-        (trigger_type == AlertTriggerType::PriceGreaterThan &&        actual_value >  trigger_value) ||
-        (trigger_type == AlertTriggerType::PriceGreaterThanEqualTo && actual_value >= trigger_value) ||
-        (trigger_type == AlertTriggerType::PriceLessThan &&           actual_value <  trigger_value) ||
-        (trigger_type == AlertTriggerType::PriceLessThanEqualTo &&    actual_value <= trigger_value);
+fn parse_on_off_value(value: &str) -> Option<bool> {
+    return match value.to_ascii_lowercase().as_str() {
+        "on" | "true" | "yes" | "1" =>  Some(true),
+        "off" | "false" | "no" | "0" => Some(false),
+        _ =>                            None,
+    };
+}
 
-    return alert_triggered;
+// like convert_time_period_string_to_seconds(), but without printing errors, as we report them ourselves
+fn parse_time_period_value(value: &str) -> Option<u64> {
+    let digits = value.strip_suffix(['s', 'm', 'h']).unwrap_or(value);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    return convert_time_period_string_to_seconds(value);
+}
+
+// this version is used for Crosses trigger mode alerts, and updates the armed state as well as returning
+// whether the alert should trigger, which it only does once each time it crosses the threshold while armed.
+fn should_crossing_alert_trigger(armed: &mut Option<bool>, trigger_type: AlertTriggerType, trigger_value: f64,
+                                 hysteresis: f64, actual_value: f64) -> bool {
+    let past_threshold = should_alert_trigger(trigger_type, trigger_value, actual_value);
+
+    match *armed {
+        None => {
+            // first check, so we can't know if it's crossed yet, but if it's already past the threshold,
+            // it'll need to retreat first before it can trigger...
+            *armed = Some(!past_threshold);
+            return false;
+        },
+        Some(true) => {
+            if past_threshold {
+                *armed = Some(false);
+                return true;
+            }
+        },
+        Some(false) => {
+            let has_retreated = match trigger_type {
+                AlertTriggerType::PriceLessThan | AlertTriggerType::PriceLessThanEqualTo => {
+                    actual_value > trigger_value + hysteresis
+                },
+                AlertTriggerType::PriceGreaterThan | AlertTriggerType::PriceGreaterThanEqualTo => {
+                    actual_value < trigger_value - hysteresis
+                },
+            };
+
+            if has_retreated {
+                *armed = Some(true);
+            }
+        },
+    }
+
+    return false;
 }
 
 // this version is used for watermarks, and so maps >= to >, and <= to <, as it doesn't make sense
@@ -487,12 +1661,794 @@ fn should_alert_trigger_watermark(trigger_type: AlertTriggerType, watermark_valu
     return alert_triggered;
 }
 
-fn get_price_for_symbol(symbol: &str, prices: &[CoinPriceItem]) -> Option<f64> {
-    for price in prices {
-        if price.symbol.to_ascii_lowercase() == symbol {
-            return Some(price.current_price);
+// combines the alert with any existing ones for the provider (chain)
+#[allow(clippy::too_many_arguments)]
+fn append_alert_message(provider_alerts: &mut BTreeMap<String, ProviderDelivery>, action: &AlertAction, fiat_currency: &str, subject: &str,
+                        triggered_alert: TriggeredAlert, severity: AlertSeverity, delivery: PendingAlertDelivery, time: DateTime<Local>) {
+    let provider_delivery = provider_alerts.entry(action.get_name())
+                                           .or_insert_with(|| ProviderDelivery::new(action, fiat_currency, time));
+    provider_delivery.add_alert(subject, triggered_alert, severity, delivery);
+}
+
+// the structured version of the alert for providers, with a snapshot of the coins' prices
+fn build_triggered_alert(alert: &AlertItem, prices: &[CoinPriceItem], price_history: &PriceHistory, local_time: DateTime<Local>,
+                         message: &str) -> TriggeredAlert {
+    let coins = alert.get_coin_symbols().iter().filter_map(|symbol| get_coin_item_for_symbol(&symbol.to_ascii_lowercase(), prices))
+                                               .cloned().collect();
+
+    let mut triggered_alert = TriggeredAlert { rule: alert.description.clone(), coins, trigger_type: None, threshold: None, value: None,
+                                               message: message.to_string() };
+    if let Some(comparison) = alert.condition.get_single_comparison() {
+        triggered_alert.trigger_type = Some(comparison.trigger_type.to_string());
+        triggered_alert.threshold = Some(comparison.trigger_value);
+        triggered_alert.value = get_alert_value(comparison, prices, price_history, local_time);
+    }
+    return triggered_alert;
+}
+
+fn build_alert_history_record(alert: &AlertItem, prices: &[CoinPriceItem], local_time: DateTime<Local>, outcome: AlertOutcome) -> AlertHistoryRecord {
+    let symbols = alert.get_coin_symbols();
+
+    let price = alert.condition.get_single_comparison().and_then(|c| get_coin_item_for_symbol(&c.coin_symbol, prices))
+                                                      .map(|c| c.current_price);
+
+    return AlertHistoryRecord::new(local_time, &alert.description, &symbols.join(","), price, &alert.get_actions_name(), outcome);
+}
+
+fn get_coin_item_for_symbol<'a>(symbol: &str, prices: &'a [CoinPriceItem]) -> Option<&'a CoinPriceItem> {
+    return prices.iter().find(|price| price.symbol.to_ascii_lowercase() == symbol);
+}
+
+// Note: for PercentMove alerts, this returns the magnitude of the move, so that the comparison
+//       works for moves in either direction.
+fn get_alert_value(comparison: &AlertComparison, prices: &[CoinPriceItem], price_history: &PriceHistory, local_time: DateTime<Local>) -> Option<f64> {
+    let coin_item = get_coin_item_for_symbol(&comparison.coin_symbol, prices)?;
+
+    return match comparison.value_type {
+        AlertValueType::Price =>            Some(coin_item.current_price),
+        AlertValueType::PercentChange1h =>  coin_item.percent_change_1h,
+        AlertValueType::PercentChange24h => Some(coin_item.percent_change_24h),
+        AlertValueType::PercentMove(window) => {
+            price_history.get_largest_percent_move(&comparison.coin_symbol, window, local_time).map(|m| m.abs())
+        },
+        AlertValueType::High24h =>          coin_item.watermarks_24h.as_ref().map(|w| w.high),
+        AlertValueType::Low24h =>           coin_item.watermarks_24h.as_ref().map(|w| w.low),
+        AlertValueType::Volume24h =>        coin_item.volume_24h,
+        AlertValueType::PriceRatio | AlertValueType::PercentSpread => {
+            let pair_coin_item = get_coin_item_for_symbol(comparison.pair_symbol.as_ref()?, prices)?;
+            if pair_coin_item.current_price == 0.0 {
+                return None;
+            }
+
+            let ratio = coin_item.current_price / pair_coin_item.current_price;
+            if comparison.value_type == AlertValueType::PriceRatio { Some(ratio) } else { Some((ratio - 1.0) * 100.0) }
+        },
+    };
+}
+
+fn build_alert_message(alert: &AlertItem, prices: &[CoinPriceItem], price_history: &PriceHistory, local_time: DateTime<Local>) -> String {
+    if let Some(comparison) = alert.condition.get_single_comparison() {
+        if let (Some(coin_item), Some(current_value)) = (get_coin_item_for_symbol(&comparison.coin_symbol, prices),
+                                                         get_alert_value(comparison, prices, price_history, local_time)) {
+            return build_comparison_alert_message(comparison, coin_item.current_price, current_value, price_history, local_time);
+        }
+    }
+
+    // otherwise, it's a compound expression, so list the prices of all the coins involved
+    let mut coin_prices = Vec::new();
+    let mut coin_symbols: Vec<&str> = Vec::new();
+    for symbol in alert.condition.get_comparisons().into_iter().flat_map(|c| c.get_coin_symbols()) {
+        if coin_symbols.contains(&symbol) {
+            continue;
+        }
+        coin_symbols.push(symbol);
+
+        if let Some(coin_item) = get_coin_item_for_symbol(symbol, prices) {
+            coin_prices.push(format!("{}: {}", coin_item.symbol.to_ascii_uppercase(), smart_format(coin_item.current_price)));
+        }
+    }
+
+    return format!("Alert: '{}' triggered. Prices: {}.", alert.description, coin_prices.join(", "));
+}
+
+// The values for the placeholders in subject/message templates. For compound rules, the symbols are comma-separated,
+// the coin values are for the first coin, and the per-comparison values are empty.
+fn build_alert_template_values(alert: &AlertItem, prices: &[CoinPriceItem], price_history: &PriceHistory, local_time: DateTime<Local>,
+                               fiat_currency: &str, default_message: String) -> BTreeMap<&'static str, String> {
+    let mut values = BTreeMap::new();
+    for placeholder in TEMPLATE_PLACEHOLDERS {
+        values.insert(placeholder, String::new());
+    }
+
+    let symbols = alert.get_coin_symbols();
+    values.insert("symbol", symbols.join(","));
+    values.insert("fiat", fiat_currency.to_ascii_uppercase());
+    values.insert("time", local_time.format("%H:%M").to_string());
+    values.insert("date", local_time.format("%Y-%m-%d").to_string());
+    values.insert("rule", alert.description.clone());
+    values.insert("severity", alert.overrides.severity.to_string());
+    values.insert("message", default_message);
+
+    if let Some(coin_item) = symbols.first().and_then(|symbol| get_coin_item_for_symbol(&symbol.to_ascii_lowercase(), prices)) {
+        values.insert("name", coin_item.name.clone());
+        values.insert("price", smart_format(coin_item.current_price));
+        values.insert("change24h", format!("{:.2}%", coin_item.percent_change_24h));
+        if let Some(watermarks) = &coin_item.watermarks_24h {
+            values.insert("high24h", smart_format(watermarks.high));
+            values.insert("low24h", smart_format(watermarks.low));
+        }
+    }
+
+    if let Some(comparison) = alert.condition.get_single_comparison() {
+        let percent_suffix = if comparison.value_type.is_percentage() { "%" } else { "" };
+        values.insert("threshold", format!("{}{}", comparison.trigger_value, percent_suffix));
+        values.insert("op", comparison.trigger_type.to_string());
+        if let Some(current_value) = get_alert_value(comparison, prices, price_history, local_time) {
+            let value = if comparison.value_type.is_percentage() { format!("{:.2}%", current_value) } else { smart_format(current_value) };
+            values.insert("value", value);
+        }
+    }
+
+    return values;
+}
+
+// Renders the subject and message for the action, using the first template found for each of: the alert's
+// named template, the (first) provider's template params, then the global templates, otherwise the defaults.
+fn render_alert_text(alert_config: &AlertConfig, alert: &AlertItem, action: &AlertAction, values: &BTreeMap<&str, String>) -> (String, String) {
+    let mut templates = Vec::with_capacity(3);
+    if let Some(template) = alert.overrides.template_name.as_ref().and_then(|name| alert_config.named_templates.get(name)) {
+        templates.push(template.clone());
+    }
+    if let AlertAction::RunProvider(provider_names) = action {
+        if let Some(provider_config) = alert_config.alert_provider_configs.get(&provider_names[0]) {
+            templates.push(AlertTemplate { subject: provider_config.get_param_as_string("subjectTemplate"),
+                                           message: provider_config.get_param_as_string("messageTemplate") });
+        }
+    }
+    templates.push(alert_config.template.clone());
+
+    let subject_template = templates.iter().find_map(|t| t.subject.as_deref()).unwrap_or(DEFAULT_ALERT_SUBJECT);
+    let message_template = templates.iter().find_map(|t| t.message.as_deref()).unwrap_or("{message}");
+
+    return (render_template(subject_template, values), render_template(message_template, values));
+}
+
+// Environment variables describing the alert for 'runCommand:' actions, so scripts don't need to parse the message.
+// For compound rules, the symbols are comma-separated, and the per-comparison values are empty.
+fn build_alert_command_env_vars(alert: &AlertItem, prices: &[CoinPriceItem], price_history: &PriceHistory, local_time: DateTime<Local>,
+                                fiat_currency: &str, message: &str) -> Vec<(&'static str, String)> {
+    let symbols = alert.get_coin_symbols();
+
+    let mut price = String::new();
+    let mut value = String::new();
+    let mut threshold = String::new();
+    let mut trigger_type = String::new();
+    if let Some(comparison) = alert.condition.get_single_comparison() {
+        if let Some(coin_item) = get_coin_item_for_symbol(&comparison.coin_symbol, prices) {
+            price = coin_item.current_price.to_string();
+        }
+        if let Some(current_value) = get_alert_value(comparison, prices, price_history, local_time) {
+            value = current_value.to_string();
+        }
+        threshold = comparison.trigger_value.to_string();
+        trigger_type = format!("{} {}", comparison.value_type, comparison.trigger_type);
+    }
+
+    return vec![("CRYPTMON_ALERT_RULE", alert.description.clone()),
+                ("CRYPTMON_ALERT_SYMBOL", symbols.join(",")),
+                ("CRYPTMON_ALERT_PRICE", price),
+                ("CRYPTMON_ALERT_VALUE", value),
+                ("CRYPTMON_ALERT_THRESHOLD", threshold),
+                ("CRYPTMON_ALERT_TRIGGER_TYPE", trigger_type),
+                ("CRYPTMON_ALERT_FIAT", fiat_currency.to_ascii_uppercase()),
+                ("CRYPTMON_ALERT_SEVERITY", alert.overrides.severity.to_string()),
+                ("CRYPTMON_ALERT_MESSAGE", message.to_string())];
+}
+
+fn build_comparison_alert_message(comparison: &AlertComparison, current_price: f64, current_value: f64, price_history: &PriceHistory,
+                                  local_time: DateTime<Local>) -> String {
+    let symbol = comparison.coin_symbol.to_ascii_uppercase();
+    return match comparison.value_type {
+        AlertValueType::Price =>            format!("Coin: {} is at price: {}.", symbol, &smart_format(current_price)),
+        AlertValueType::PercentChange1h =>  format!("Coin: {} has changed {:.2}% in 1h, price: {}.", symbol, current_value,
+                                                        &smart_format(current_price)),
+        AlertValueType::PercentChange24h => format!("Coin: {} has changed {:.2}% in 24h, price: {}.", symbol, current_value,
+                                                        &smart_format(current_price)),
+        AlertValueType::PercentMove(window) => {
+            // get the signed version for display, so the direction is clear
+            let signed_move = price_history.get_largest_percent_move(&comparison.coin_symbol, window, local_time).unwrap_or(current_value);
+            format!("Coin: {} has moved {:.2}% within {}, price: {}.", symbol, signed_move, format_time_period(window),
+                        &smart_format(current_price))
+        },
+        AlertValueType::High24h | AlertValueType::Low24h | AlertValueType::Volume24h => {
+            format!("Coin: {} {} is at: {}, price: {}.", symbol, comparison.value_type, &smart_format(current_value),
+                        &smart_format(current_price))
+        },
+        AlertValueType::PriceRatio =>       format!("Coins: {} price ratio is at: {}.", comparison.get_operand_name(),
+                                                        &smart_format(current_value)),
+        AlertValueType::PercentSpread =>    format!("Coins: {} price spread is at: {:.2}%.", comparison.get_operand_name(), current_value),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::alert_expression::parse_trigger_type;
+
+    use std::cell::{Cell, RefCell};
+
+    struct FakeClock {
+        time:       Cell<DateTime<Local>>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> DateTime<Local> {
+            return self.time.get();
+        }
+
+        fn sleep(&self, duration: std::time::Duration) {
+            self.time.set(self.time.get() + Duration::seconds(duration.as_secs() as i64));
+        }
+    }
+
+    struct FakePriceProvider {
+        prices:         Rc<RefCell<Vec<CoinPriceItem>>>,
+        fetch_count:    Rc<Cell<usize>>,
+    }
+
+    impl PriceProvider for FakePriceProvider {
+        fn get_current_prices(&self) -> Result<Vec<CoinPriceItem>, crate::price_provider::GetDataError> {
+            self.fetch_count.set(self.fetch_count.get() + 1);
+            return Ok(self.prices.borrow().clone());
+        }
+    }
+
+    struct FakeAlertProvider {
+        // only the successfully sent ones
+        sent_events:        Rc<RefCell<Vec<AlertEvent>>>,
+        attempt_count:      Cell<usize>,
+        // the number of the next attempts which fail, usize::MAX to always fail
+        fail_count:         Cell<usize>,
+        // fail with QuotaExhausted rather than CantConnect
+        quota_exhausted:    bool,
+        quota_remaining:    Cell<Option<i64>>,
+    }
+
+    impl FakeAlertProvider {
+        fn new(fail_count: usize, quota_exhausted: bool) -> FakeAlertProvider {
+            return FakeAlertProvider { sent_events: Rc::new(RefCell::new(Vec::new())), attempt_count: Cell::new(0),
+                                       fail_count: Cell::new(fail_count), quota_exhausted, quota_remaining: Cell::new(None) };
+        }
+    }
+
+    impl AlertProvider for FakeAlertProvider {
+        fn send_alert(&self, event: &AlertEvent) -> Result<(), SendAlertError> {
+            self.attempt_count.set(self.attempt_count.get() + 1);
+            if self.fail_count.get() > 0 {
+                if self.fail_count.get() != usize::MAX {
+                    self.fail_count.set(self.fail_count.get() - 1);
+                }
+                if self.quota_exhausted {
+                    return Err(SendAlertError::QuotaExhausted("No credits left".to_string()));
+                }
+                return Err(SendAlertError::CantConnect("Connection refused".to_string()));
+            }
+
+            self.sent_events.borrow_mut().push(event.clone());
+            return Ok(());
+        }
+
+        fn get_quota_remaining(&self) -> Option<i64> {
+            return self.quota_remaining.get();
+        }
+    }
+
+    struct TestHarness {
+        service:        AlertService,
+        clock:          Rc<FakeClock>,
+        prices:         Rc<RefCell<Vec<CoinPriceItem>>>,
+        fetch_count:    Rc<Cell<usize>>,
+        // "fake" works unless its fail_count is set, "backup" always works, "broken" always fails to connect,
+        // and "nocredit" has always run out of quota
+        alert_providers: BTreeMap<String, Rc<FakeAlertProvider>>,
+        // the events sent with "fake"
+        sent_events:    Rc<RefCell<Vec<AlertEvent>>>,
+    }
+
+    impl TestHarness {
+        fn new(config_contents: &str) -> TestHarness {
+            let config = Config::from_config_string(config_contents);
+
+            let start_time = Local.from_local_datetime(&chrono::NaiveDate::from_ymd_opt(2024, 3, 4).unwrap().and_hms_opt(12, 0, 0).unwrap()).unwrap();
+            let clock = Rc::new(FakeClock { time: Cell::new(start_time) });
+            let prices = Rc::new(RefCell::new(Vec::new()));
+            let fetch_count = Rc::new(Cell::new(0));
+
+            let mut alert_providers = BTreeMap::new();
+            alert_providers.insert("fake".to_string(), Rc::new(FakeAlertProvider::new(0, false)));
+            alert_providers.insert("backup".to_string(), Rc::new(FakeAlertProvider::new(0, false)));
+            alert_providers.insert("broken".to_string(), Rc::new(FakeAlertProvider::new(usize::MAX, false)));
+            alert_providers.insert("nocredit".to_string(), Rc::new(FakeAlertProvider::new(usize::MAX, true)));
+            let sent_events = alert_providers["fake"].sent_events.clone();
+
+            let service_alert_providers = alert_providers.iter().map(|(name, provider)| (name.clone(), provider.clone() as Rc<dyn AlertProvider>)).collect();
+            let service = AlertService::new_with_dependencies(&config, &PriceProviderParams::new(),
+                                                              Box::new(FakePriceProvider { prices: prices.clone(), fetch_count: fetch_count.clone() }),
+                                                              service_alert_providers, clock.clone()).unwrap();
+
+            return TestHarness { service, clock, prices, fetch_count, alert_providers, sent_events };
+        }
+
+        fn attempt_count(&self, provider_name: &str) -> usize {
+            return self.alert_providers[provider_name].attempt_count.get();
+        }
+
+        fn sent_count(&self, provider_name: &str) -> usize {
+            return self.alert_providers[provider_name].sent_events.borrow().len();
+        }
+
+        // sets the prices the fake provider returns, advances the clock, and runs a single check
+        fn check_after(&mut self, minutes: i64, coin_prices: &[(&str, f64)]) -> usize {
+            *self.prices.borrow_mut() = coin_prices.iter().map(|(symbol, price)| CoinPriceItem::new_basic(symbol, symbol, *price)).collect();
+            self.clock.sleep(std::time::Duration::from_secs(minutes as u64 * 60));
+
+            let sent_before = self.sent_events.borrow().len();
+            assert!(self.service.check_once());
+            return self.sent_events.borrow().len() - sent_before;
         }
     }
 
-    return None;
+    #[test]
+    fn test_should_alert_trigger_01() {
+        let check = |comparison: &str, trigger_value: f64, actual_value: f64| {
+            should_alert_trigger(parse_trigger_type(comparison).unwrap(), trigger_value, actual_value)
+        };
+
+        assert!(check("<", 40000.0, 39999.0));
+        assert!(!check("<", 40000.0, 40000.0));
+        assert!(check("<=", 40000.0, 40000.0));
+        assert!(!check("<=", 40000.0, 40000.5));
+        assert!(check(">", 40000.0, 40000.5));
+        assert!(!check(">", 40000.0, 40000.0));
+        assert!(check(">=", 40000.0, 40000.0));
+        assert!(!check(">=", 40000.0, 39999.0));
+        // percentage values can be negative
+        assert!(check("<=", -8.0, -8.5));
+        assert!(!check("<=", -8.0, -7.5));
+    }
+
+    #[test]
+    fn test_should_alert_trigger_watermark_01() {
+        // equal to the watermark is never a trip, even for the '=' comparisons
+        assert!(!should_alert_trigger_watermark(AlertTriggerType::PriceLessThanEqualTo, 39000.0, 39000.0));
+        assert!(!should_alert_trigger_watermark(AlertTriggerType::PriceGreaterThanEqualTo, 51000.0, 51000.0));
+
+        assert!(should_alert_trigger_watermark(AlertTriggerType::PriceLessThan, 39000.0, 38999.0));
+        assert!(should_alert_trigger_watermark(AlertTriggerType::PriceLessThanEqualTo, 39000.0, 38999.0));
+        assert!(!should_alert_trigger_watermark(AlertTriggerType::PriceLessThan, 39000.0, 39001.0));
+
+        assert!(should_alert_trigger_watermark(AlertTriggerType::PriceGreaterThan, 51000.0, 51001.0));
+        assert!(should_alert_trigger_watermark(AlertTriggerType::PriceGreaterThanEqualTo, 51000.0, 51001.0));
+        assert!(!should_alert_trigger_watermark(AlertTriggerType::PriceGreaterThan, 51000.0, 50999.0));
+    }
+
+    #[test]
+    fn test_should_crossing_alert_trigger_01() {
+        let mut armed = None;
+        let mut check = |actual_value: f64| should_crossing_alert_trigger(&mut armed, AlertTriggerType::PriceLessThanEqualTo,
+                                                                           40000.0, 500.0, actual_value);
+
+        // already past the threshold at the start, so it has to retreat first
+        assert!(!check(39000.0));
+        assert!(!check(40200.0));
+        // not past the hysteresis band yet
+        assert!(!check(39800.0));
+        assert!(!check(40600.0));
+        assert!(check(40000.0));
+        // only once per crossing
+        assert!(!check(39000.0));
+        assert!(!check(40501.0));
+        assert!(check(39999.0));
+    }
+
+    #[test]
+    fn test_parse_alert_function_rule_01() {
+        let (comparison, trigger_mode, action, _, overrides) =
+                    parse_alert_function_rule("alert(BTC, change24h% <=, -8, textbelt, sleep=6h, severity=critical)").unwrap();
+        assert_eq!(comparison.to_string(), "BTC.change24h <= -8%");
+        assert_eq!(trigger_mode, AlertTriggerMode::Level);
+        assert_eq!(action, "textbelt");
+        assert_eq!(overrides.sleep_period, Some(6 * 60 * 60));
+        assert_eq!(overrides.severity, AlertSeverity::Critical);
+
+        let (_, trigger_mode, _, _, _) = parse_alert_function_rule("alert(BTC, crosses <=, 40000, print, hysteresis=2%)").unwrap();
+        assert_eq!(trigger_mode, AlertTriggerMode::Crosses(800.0));
+
+        let err = parse_alert_function_rule("alert(BTC, <=, 40000)").unwrap_err();
+        assert_eq!(err.column, 21);
+        let err = parse_alert_function_rule("alert(BTC, =>, 40000, print)").unwrap_err();
+        assert_eq!(err.column, 12);
+        let err = parse_alert_function_rule("alert(BTC, <=, 4000O, print)").unwrap_err();
+        assert_eq!(err.column, 16);
+        let err = parse_alert_function_rule("alert(BTC, <=, 40000, print, snooze=1h)").unwrap_err();
+        assert_eq!(err.column, 30);
+    }
+
+    #[test]
+    fn test_evaluate_sleep_and_watermark_01() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 0s\n\
+                                            alerts.perAlertSleepPeriod: 10m\n\
+                                            alerts.watermarkTripSleepEnabled: true\n\
+                                            alerts.watermarkTripSleepPeriod: 1h\n\
+                                            alerts.newAlert: alert(BTC, <, 40000, fake)\n");
+
+        assert_eq!(harness.check_after(0, &[("BTC", 41000.0)]), 0);
+        assert_eq!(harness.check_after(2, &[("BTC", 39000.0)]), 1);
+        // within the per-alert sleep period, even though it's lower
+        assert_eq!(harness.check_after(2, &[("BTC", 38000.0)]), 0);
+        // after that, but within the watermark trip sleep, it needs to be lower than when it last fired
+        assert_eq!(harness.check_after(10, &[("BTC", 39500.0)]), 0);
+        assert_eq!(harness.check_after(2, &[("BTC", 38500.0)]), 1);
+
+        let sent_events = harness.sent_events.borrow();
+        assert_eq!(sent_events[1].alerts[0].value, Some(38500.0));
+    }
+
+    #[test]
+    fn test_evaluate_sleep_override_01() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 0s\n\
+                                            alerts.perAlertSleepPeriod: 1h\n\
+                                            alerts.watermarkTripSleepEnabled: false\n\
+                                            alerts.combineMultipleAlerts: false\n\
+                                            alerts.newAlert: alert(BTC, <, 40000, fake, sleep=10m)\n\
+                                            alerts.newAlert: alert(ETH, >, 3000, backup)\n");
+        let prices = [("BTC", 39000.0), ("ETH", 3100.0)];
+
+        assert_eq!(harness.check_after(0, &prices), 1);
+        assert_eq!(harness.sent_count("backup"), 1);
+        assert_eq!(harness.check_after(5, &prices), 0);
+
+        // the alert with its own sleep period fires again after it, while the other one is still sleeping
+        assert_eq!(harness.check_after(5, &prices), 1);
+        assert_eq!(harness.check_after(10, &prices), 1);
+        assert_eq!(harness.sent_count("backup"), 1);
+
+        assert_eq!(harness.check_after(40, &prices), 1);
+        assert_eq!(harness.sent_count("backup"), 2);
+    }
+
+    #[test]
+    fn test_evaluate_combine_01() {
+        let rules = "alerts.globalSleepPeriod: 0s\n\
+                     alerts.newAlert: alert(BTC, <, 40000, fake)\n\
+                     alerts.newAlert: alert(ETH, >, 3000, fake)\n\
+                     alerts.newAlert: alert(LTC, >, 100, fake)\n";
+        let prices = [("BTC", 39000.0), ("ETH", 3100.0), ("LTC", 90.0)];
+
+        let mut harness = TestHarness::new(&format!("alerts.combineMultipleAlerts: true\n{}", rules));
+        assert_eq!(harness.check_after(0, &prices), 1);
+        assert_eq!(harness.sent_events.borrow()[0].alerts.len(), 2);
+
+        let mut harness = TestHarness::new(&format!("alerts.combineMultipleAlerts: false\n{}", rules));
+        assert_eq!(harness.check_after(0, &prices), 2);
+        assert!(harness.sent_events.borrow().iter().all(|event| event.alerts.len() == 1));
+    }
+
+    #[test]
+    fn test_evaluate_check_periods_01() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 0s\n\
+                                            alerts.combineMultipleAlerts: false\n\
+                                            alerts.coinCheckPeriods: ETH=4m\n\
+                                            alerts.newAlert: alert(BTC, <, 40000, fake, sleep=off, checkPeriod=10m)\n\
+                                            alerts.newAlert: alert(ETH, >, 3000, fake, sleep=off)\n");
+        let prices = [("BTC", 39000.0), ("ETH", 3100.0)];
+
+        assert_eq!(harness.check_after(0, &prices), 2);
+        assert_eq!(harness.check_after(2, &prices), 0);
+        assert_eq!(harness.check_after(2, &prices), 1);
+        // a slow check doesn't push the next ones back
+        assert_eq!(harness.check_after(7, &prices), 2);
+        assert_eq!(harness.check_after(1, &prices), 1);
+    }
+
+    #[test]
+    fn test_evaluate_digest_01() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 0s\n\
+                                            alerts.newDigest: daily 12:10, fake\n\
+                                            alerts.newAlert: alert(BTC, <, 40000, print)\n\
+                                            alerts.newAlert: alert(ETH, >, 3000, print)\n");
+        let prices = [("BTC", 41000.0), ("ETH", 2900.0), ("LTC", 90.0)];
+
+        assert_eq!(harness.check_after(0, &prices), 0);
+        assert_eq!(harness.check_after(10, &prices), 1);
+        assert_eq!(harness.check_after(2, &prices), 0);
+
+        let sent_events = harness.sent_events.borrow();
+        assert_eq!(sent_events[0].subject, "Cryptmon Daily Summary");
+        assert_eq!(sent_events[0].render_text(), "BTC 41,000.00 +0.00%, ETH 2,900.00 +0.00%");
+    }
+
+    #[test]
+    fn test_evaluate_quiet_hours_01() {
+        let rules = "alerts.globalSleepPeriod: 0s\n\
+                     alerts.combineMultipleAlerts: false\n\
+                     alerts.quietHours: 12:00-12:30\n\
+                     alerts.newAlert: alert(BTC, <, 40000, fake)\n\
+                     alerts.newAlert: alert(ETH, <, 2000, fake, quietHours=off)\n";
+
+        let mut harness = TestHarness::new(&format!("alerts.quietHoursAction: queue\n{}", rules));
+        // only the alert which ignores quiet hours is sent during them
+        assert_eq!(harness.check_after(0, &[("BTC", 39000.0), ("ETH", 1900.0)]), 1);
+        assert_eq!(harness.check_after(10, &[("BTC", 38000.0), ("ETH", 2100.0)]), 0);
+        // and the queued one is sent once they end
+        assert_eq!(harness.check_after(20, &[("BTC", 41000.0), ("ETH", 2100.0)]), 1);
+        {
+            let sent_events = harness.sent_events.borrow();
+            assert_eq!(sent_events[1].header.as_deref(), Some("Alerts during quiet hours:"));
+            assert_eq!(sent_events[1].alerts[0].value, Some(39000.0));
+        }
+
+        let mut harness = TestHarness::new(&format!("alerts.quietHoursAction: hold\n{}", rules));
+        assert_eq!(harness.check_after(0, &[("BTC", 39000.0), ("ETH", 2100.0)]), 0);
+        // held alerts aren't sent at all, but can trigger again afterwards
+        assert_eq!(harness.check_after(30, &[("BTC", 41000.0), ("ETH", 2100.0)]), 0);
+        assert_eq!(harness.check_after(2, &[("BTC", 39500.0), ("ETH", 2100.0)]), 1);
+        assert_eq!(harness.sent_events.borrow()[0].header, None);
+    }
+
+    #[test]
+    fn test_evaluate_quiet_hours_02() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 0s\n\
+                                            alerts.quietHours: 12:00-12:30\n\
+                                            alerts.quietHoursAction: hold\n\
+                                            alerts.newAlert: alert(BTC, crosses <, 40000, fake)\n");
+
+        assert_eq!(harness.check_after(0, &[("BTC", 41000.0)]), 0);
+        // a crossing alert which is held during quiet hours stays armed...
+        assert_eq!(harness.check_after(10, &[("BTC", 39000.0)]), 0);
+        assert_eq!(harness.check_after(10, &[("BTC", 39000.0)]), 0);
+        // so it fires once they end if it's still past the threshold
+        assert_eq!(harness.check_after(15, &[("BTC", 39000.0)]), 1);
+        assert_eq!(harness.check_after(5, &[("BTC", 39000.0)]), 0);
+    }
+
+    #[test]
+    fn test_evaluate_global_sleep_01() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 30m\n\
+                                            alerts.perAlertSleepPeriod: 1m\n\
+                                            alerts.newAlert: alert(BTC, <, 40000, fake)\n\
+                                            alerts.newAlert: alert(ETH, >, 3000, fake)\n");
+
+        // the global sleep only starts once something has actually been sent
+        assert_eq!(harness.check_after(0, &[("BTC", 41000.0), ("ETH", 2900.0)]), 0);
+        assert_eq!(harness.check_after(5, &[("BTC", 39000.0), ("ETH", 2900.0)]), 1);
+        assert_eq!(harness.fetch_count.get(), 2);
+
+        // and the prices aren't fetched during it
+        assert_eq!(harness.check_after(5, &[("BTC", 39000.0), ("ETH", 3100.0)]), 0);
+        assert_eq!(harness.fetch_count.get(), 2);
+
+        assert_eq!(harness.check_after(25, &[("BTC", 39000.0), ("ETH", 3100.0)]), 1);
+        assert_eq!(harness.fetch_count.get(), 3);
+        assert_eq!(harness.sent_events.borrow()[1].alerts.len(), 2);
+    }
+
+    #[test]
+    fn test_evaluate_global_sleep_02() {
+        let ack_file_path = std::env::temp_dir().join(format!("cryptmon_global_sleep_ack_test_{}.txt", std::process::id()));
+        let ack_file_path = ack_file_path.to_str().unwrap();
+        let _ = std::fs::remove_file(ack_file_path);
+
+        let mut harness = TestHarness::new(&format!("alerts.globalSleepPeriod: 1h\n\
+                                                     alerts.perAlertSleepPeriod: 1m\n\
+                                                     alerts.ackFile: {}\n\
+                                                     alerts.newAlert: alert(BTC, <, 40000, fake, escalate=[backup], escalateAfter=10m)\n",
+                                                    ack_file_path));
+
+        assert_eq!(harness.check_after(0, &[("BTC", 39000.0)]), 1);
+        assert_eq!(harness.check_after(5, &[("BTC", 39000.0)]), 0);
+        assert_eq!(harness.fetch_count.get(), 1);
+
+        // the prices are still fetched during the global sleep when an escalation is due
+        harness.check_after(5, &[("BTC", 39000.0)]);
+        assert_eq!(harness.fetch_count.get(), 2);
+        assert_eq!(harness.sent_count("backup"), 1);
+
+        // and acknowledgements are still handled without them
+        crate::alert_ack::write_alert_acknowledgement(ack_file_path, "BTC").unwrap();
+        harness.check_after(2, &[("BTC", 39000.0)]);
+        assert_eq!(harness.fetch_count.get(), 2);
+        assert_eq!(harness.service.alert_items[0].next_escalation_time, None);
+
+        harness.check_after(10, &[("BTC", 39000.0)]);
+        assert_eq!(harness.fetch_count.get(), 2);
+        assert_eq!(harness.sent_count("backup"), 1);
+
+        let _ = std::fs::remove_file(ack_file_path);
+    }
+
+    #[test]
+    fn test_evaluate_retry_01() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 0s\n\
+                                            alerts.perAlertSleepPeriod: 30m\n\
+                                            alerts.retryInitialDelay: 1m\n\
+                                            alerts.retryMaxAge: 10m\n\
+                                            alerts.newAlert: alert(BTC, <, 40000, fake)\n");
+        harness.alert_providers["fake"].fail_count.set(3);
+        let start_time = harness.clock.now();
+
+        // a failed send is queued, and the alert doesn't start sleeping
+        assert_eq!(harness.check_after(0, &[("BTC", 39000.0)]), 0);
+        assert_eq!(harness.attempt_count("fake"), 1);
+        assert_eq!(harness.service.retry_queue.len(), 1);
+        assert_eq!(harness.service.retry_queue[0].next_attempt_time, start_time + Duration::minutes(1));
+        assert!(harness.service.alert_items[0].delivery_pending);
+        assert!(harness.service.alert_items[0].sleep_until <= start_time);
+
+        // and it isn't sent again by the alert triggering while it's waiting to be retried
+        assert_eq!(harness.check_after(1, &[("BTC", 39000.0)]), 0);
+        assert_eq!(harness.attempt_count("fake"), 2);
+        assert_eq!(harness.service.retry_queue[0].next_attempt_time, start_time + Duration::minutes(3));
+
+        // the delay doubles each time
+        assert_eq!(harness.check_after(1, &[("BTC", 39000.0)]), 0);
+        assert_eq!(harness.attempt_count("fake"), 2);
+        assert_eq!(harness.check_after(1, &[("BTC", 39000.0)]), 0);
+        assert_eq!(harness.attempt_count("fake"), 3);
+        assert_eq!(harness.service.retry_queue[0].next_attempt_time, start_time + Duration::minutes(7));
+
+        // the per-alert sleep only starts once it's actually been sent
+        assert_eq!(harness.check_after(4, &[("BTC", 39000.0)]), 1);
+        assert_eq!(harness.attempt_count("fake"), 4);
+        assert!(harness.service.retry_queue.is_empty());
+        assert!(!harness.service.alert_items[0].delivery_pending);
+        assert_eq!(harness.service.alert_items[0].sleep_until, start_time + Duration::minutes(37));
+
+        // once it's been failing for longer than the max age, it's dropped after the last attempt
+        harness.alert_providers["fake"].fail_count.set(usize::MAX);
+        assert_eq!(harness.check_after(30, &[("BTC", 39000.0)]), 0);
+        assert_eq!(harness.attempt_count("fake"), 5);
+        for (minutes, attempt_count) in [(1, 6), (2, 7), (4, 8)] {
+            assert_eq!(harness.check_after(minutes, &[("BTC", 39000.0)]), 0);
+            assert_eq!(harness.attempt_count("fake"), attempt_count);
+            assert_eq!(harness.service.retry_queue.len(), 1);
+        }
+        // (the price has gone back up, otherwise the alert would trigger again now it's been dropped)
+        assert_eq!(harness.check_after(8, &[("BTC", 41000.0)]), 0);
+        assert_eq!(harness.attempt_count("fake"), 9);
+        assert!(harness.service.retry_queue.is_empty());
+        assert!(!harness.service.alert_items[0].delivery_pending);
+    }
+
+    #[test]
+    fn test_evaluate_multiple_actions_01() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 0s\n\
+                                            alerts.perAlertSleepPeriod: 10m\n\
+                                            alerts.combineMultipleAlerts: false\n\
+                                            alerts.newAlert: alert(BTC, <, 40000, [fake, backup])\n\
+                                            alerts.newAlert: alert(ETH, >, 3000, broken|backup)\n\
+                                            alerts.newAlert: alert(SOL, >, 100, fake|backup)\n\
+                                            alerts.newAlert: alert(XRP, >, 1, nocredit|backup)\n");
+
+        // all the actions in a list are run
+        assert_eq!(harness.check_after(0, &[("BTC", 39000.0)]), 1);
+        assert_eq!(harness.sent_count("backup"), 1);
+
+        // the fallback is used when the first provider fails
+        harness.check_after(20, &[("ETH", 3100.0)]);
+        assert_eq!(harness.attempt_count("broken"), 1);
+        assert_eq!(harness.sent_count("backup"), 2);
+        assert_eq!(harness.sent_count("fake"), 1);
+
+        // but not if it works
+        assert_eq!(harness.check_after(20, &[("SOL", 110.0)]), 1);
+        assert_eq!(harness.sent_count("backup"), 2);
+
+        // and once a provider has run out of quota, it isn't tried again
+        harness.check_after(20, &[("XRP", 1.5)]);
+        assert_eq!(harness.attempt_count("nocredit"), 1);
+        assert_eq!(harness.sent_count("backup"), 3);
+        harness.check_after(20, &[("XRP", 1.5)]);
+        assert_eq!(harness.attempt_count("nocredit"), 1);
+        assert_eq!(harness.sent_count("backup"), 4);
+        assert!(harness.service.retry_queue.is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_quota_combine_01() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 0s\n\
+                                            alerts.perAlertSleepPeriod: 1m\n\
+                                            alerts.combineMultipleAlerts: false\n\
+                                            alerts.quotaWarningRemaining: 5\n\
+                                            alerts.newAlert: alert(BTC, <, 40000, nocredit|backup)\n\
+                                            alerts.newAlert: alert(ETH, >, 3000, nocredit|backup)\n");
+        let prices = [("BTC", 39000.0), ("ETH", 3100.0)];
+
+        harness.check_after(0, &prices);
+        assert_eq!(harness.attempt_count("nocredit"), 1);
+        assert_eq!(harness.sent_count("backup"), 2);
+
+        // the primary having run out doesn't mean the fallback is low...
+        harness.alert_providers["backup"].quota_remaining.set(Some(50));
+        harness.check_after(5, &prices);
+        assert_eq!(harness.sent_count("backup"), 4);
+
+        // but once the fallback is (as reported when sending the next alerts), the alerts are combined
+        harness.alert_providers["backup"].quota_remaining.set(Some(3));
+        harness.check_after(5, &prices);
+        assert_eq!(harness.sent_count("backup"), 6);
+        harness.check_after(5, &prices);
+        assert_eq!(harness.sent_count("backup"), 7);
+        assert_eq!(harness.alert_providers["backup"].sent_events.borrow()[6].alerts.len(), 2);
+        assert_eq!(harness.attempt_count("nocredit"), 1);
+    }
+
+    #[test]
+    fn test_evaluate_escalation_01() {
+        let ack_file_path = std::env::temp_dir().join(format!("cryptmon_escalation_ack_test_{}.txt", std::process::id()));
+        let ack_file_path = ack_file_path.to_str().unwrap();
+        let _ = std::fs::remove_file(ack_file_path);
+
+        let mut harness = TestHarness::new(&format!("alerts.globalSleepPeriod: 0s\n\
+                                                     alerts.perAlertSleepPeriod: 1h\n\
+                                                     alerts.ackFile: {}\n\
+                                                     alerts.newAlert: alert(BTC, <, 40000, fake, escalate=[backup, fake], escalateAfter=10m)\n",
+                                                    ack_file_path));
+
+        assert_eq!(harness.check_after(0, &[("BTC", 39000.0)]), 1);
+        assert_eq!(harness.check_after(5, &[("BTC", 39000.0)]), 0);
+        assert_eq!(harness.sent_count("backup"), 0);
+
+        // it's re-sent through the next escalation action after the period, even though the alert is sleeping
+        harness.check_after(5, &[("BTC", 39000.0)]);
+        assert_eq!(harness.sent_count("backup"), 1);
+        assert_eq!(harness.service.alert_items[0].escalation_level, 1);
+        assert_eq!(harness.check_after(10, &[("BTC", 39000.0)]), 1);
+        // and the last one repeats
+        assert_eq!(harness.check_after(10, &[("BTC", 39000.0)]), 1);
+        assert_eq!(harness.sent_count("backup"), 1);
+
+        // it stops once the condition clears
+        harness.check_after(5, &[("BTC", 41000.0)]);
+        assert_eq!(harness.service.alert_items[0].escalation_level, 0);
+        assert_eq!(harness.service.alert_items[0].next_escalation_time, None);
+        assert_eq!(harness.check_after(10, &[("BTC", 41000.0)]), 0);
+        assert_eq!(harness.sent_count("backup"), 1);
+
+        // and once it's been acknowledged
+        assert_eq!(harness.check_after(20, &[("BTC", 39000.0)]), 1);
+        harness.check_after(10, &[("BTC", 39000.0)]);
+        assert_eq!(harness.sent_count("backup"), 2);
+        crate::alert_ack::write_alert_acknowledgement(ack_file_path, "BTC").unwrap();
+        harness.check_after(1, &[("BTC", 39000.0)]);
+        assert_eq!(harness.service.alert_items[0].next_escalation_time, None);
+        for _ in 0..3 {
+            assert_eq!(harness.check_after(10, &[("BTC", 39000.0)]), 0);
+        }
+        assert_eq!(harness.sent_count("backup"), 2);
+
+        let _ = std::fs::remove_file(ack_file_path);
+    }
+
+    #[test]
+    fn test_run_backtest_01() {
+        let mut harness = TestHarness::new("alerts.globalSleepPeriod: 30m\n\
+                                            alerts.newAlert: alert(BTC, <, 40000, fake)\n\
+                                            alerts.newAlert: alert(ETH, crosses >, 3000, fake)\n");
+
+        let start_time = harness.clock.now();
+        let price_records = [(0, 39000.0, 2900.0), (10, 41000.0, 3100.0), (20, 41000.0, 3100.0), (40, 41000.0, 3100.0), (50, 41000.0, 3100.0)]
+                                .iter().map(|(minutes, btc_price, eth_price)| {
+                                    (start_time + Duration::minutes(*minutes), vec![CoinPriceItem::new_basic("BTC", "BTC", *btc_price), CoinPriceItem::new_basic("ETH", "ETH", *eth_price)])
+                                }).collect();
+
+        let records = harness.service.run_backtest(price_records);
+        let outcomes: Vec<(&str, AlertOutcome)> = records.iter().map(|r| (r.symbols.as_str(), r.outcome)).collect();
+        // the crossing during the global sleep is suppressed, but it fires once the sleep has ended, as it would live
+        assert_eq!(outcomes, vec![("BTC", AlertOutcome::Fired), ("ETH", AlertOutcome::GlobalSleepSuppressed),
+                                  ("ETH", AlertOutcome::GlobalSleepSuppressed), ("ETH", AlertOutcome::Fired)]);
+        assert_eq!(records[3].timestamp, (start_time + Duration::minutes(40)).to_rfc3339());
+        assert!(harness.sent_events.borrow().is_empty());
+    }
 }
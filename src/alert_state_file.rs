@@ -0,0 +1,130 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+const STATE_FILE_VERSION: u32 = 1;
+
+// The state of an alert which needs to survive restarts, so that alerts which have already
+// triggered don't immediately trigger again.
+// Times are unix timestamps in seconds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersistedAlertState {
+    pub crossing_armed:             Option<bool>,
+    pub previous_alert_watermark:   Option<f64>,
+    pub watermark_trip_sleep_until: Option<i64>,
+    pub sleep_until:                i64,
+
+    // for escalating alerts, the number of escalations so far and when the next one is due
+    #[serde(default)]
+    pub escalation_level:           u32,
+    #[serde(default)]
+    pub next_escalation_time:       Option<i64>,
+    // whether the escalation was acknowledged, in which case it won't escalate again until the condition clears
+    #[serde(default)]
+    pub escalation_acknowledged:    bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AlertStateFile {
+    version:    u32,
+
+    // key = alert identity (see get_alert_identity())
+    alerts:     BTreeMap<String, PersistedAlertState>,
+}
+
+// The identity of an alert for the state file, which is the rule text with whitespace normalised, so that
+// the state of rules which have changed isn't used.
+pub fn get_alert_identity(rule_text: &str) -> String {
+    return rule_text.split_whitespace().collect::<Vec<&str>>().join(" ");
+}
+
+// Returns an empty map if the file doesn't exist yet, or can't be read.
+pub fn load_alert_states(path: &str) -> BTreeMap<String, PersistedAlertState> {
+    let contents = std::fs::read_to_string(path);
+    if contents.is_err() {
+        // it's likely just the first run...
+        return BTreeMap::new();
+    }
+
+    match serde_json::from_str::<AlertStateFile>(&contents.unwrap()) {
+        Ok(state_file) if state_file.version == STATE_FILE_VERSION => {
+            return state_file.alerts;
+        },
+        Ok(_) => {
+            eprintln!("Warning: Unsupported alert state file version in: '{}', it will be ignored.", path);
+        },
+        Err(err) => {
+            eprintln!("Warning: Couldn't parse alert state file: '{}', it will be ignored. Error: {}", path, err);
+        }
+    }
+
+    return BTreeMap::new();
+}
+
+pub fn save_alert_states(path: &str, alerts: &BTreeMap<String, PersistedAlertState>) -> Result<(), String> {
+    let state_file = AlertStateFile { version: STATE_FILE_VERSION, alerts: alerts.clone() };
+    let contents = serde_json::to_string_pretty(&state_file).map_err(|e| e.to_string())?;
+
+    // write to a temp file first and then rename it, so we don't end up with a partially-written file
+    let temp_path = format!("{}.tmp", path);
+    std::fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+    std::fs::rename(&temp_path, path).map_err(|e| e.to_string())?;
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load_round_trip_01() {
+        let path = std::env::temp_dir().join(format!("cryptmon_alert_state_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut alerts = BTreeMap::new();
+        alerts.insert(get_alert_identity("alert(BTC,  <=, 45000,   print)"),
+                      PersistedAlertState { crossing_armed: Some(true), previous_alert_watermark: Some(44000.0),
+                                            watermark_trip_sleep_until: None, sleep_until: 1660000000,
+                                            escalation_level: 1, next_escalation_time: Some(1660001800), escalation_acknowledged: false });
+        save_alert_states(path, &alerts).unwrap();
+
+        let loaded = load_alert_states(path);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded, alerts);
+        assert!(loaded.contains_key("alert(BTC, <=, 45000, print)"));
+        assert!(load_alert_states(path).is_empty());
+    }
+
+    #[test]
+    fn test_load_old_fields_01() {
+        let path = std::env::temp_dir().join(format!("cryptmon_alert_state_old_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        // files from before 'hasTriggered' was removed (and the escalation fields were added) can still be loaded
+        std::fs::write(path, r#"{"version": 1, "alerts": {"alert(BTC, <=, 45000, print)": {"hasTriggered": false, "crossingArmed": null,
+                                 "previousAlertWatermark": null, "watermarkTripSleepUntil": null, "sleepUntil": 1660000000}}}"#).unwrap();
+        let loaded = load_alert_states(path);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded["alert(BTC, <=, 45000, print)"].sleep_until, 1660000000);
+        assert_eq!(loaded["alert(BTC, <=, 45000, print)"].escalation_level, 0);
+    }
+}
@@ -0,0 +1,74 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use std::collections::BTreeMap;
+
+pub const DEFAULT_ALERT_SUBJECT: &str = "Cryptmon Price Alert";
+
+// The placeholders which can be used in templates, i.e. "{symbol} is at {price} {fiat}".
+// 'message' is the default alert message, and 'value' is the value the alert compares (i.e. the percentage
+// change for change24h% alerts).
+pub const TEMPLATE_PLACEHOLDERS: [&str; 15] = ["symbol", "name", "price", "fiat", "threshold", "op", "value", "change24h",
+                                               "high24h", "low24h", "time", "date", "rule", "severity", "message"];
+
+// optional subject and message templates, where None means use the next one in the chain, and then the default
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AlertTemplate {
+    pub subject:    Option<String>,
+    pub message:    Option<String>,
+}
+
+// Replaces "{name}" placeholders in the template with the values, along with "\n" with newlines.
+// Unknown placeholders are left as-is.
+pub fn render_template(template: &str, values: &BTreeMap<&str, String>) -> String {
+    let mut result = String::with_capacity(template.len());
+
+    let mut remaining = template;
+    while let Some(start) = remaining.find('{') {
+        result.push_str(&remaining[..start]);
+        let placeholder_text = &remaining[start..];
+
+        let end = placeholder_text.find('}');
+        let value = end.and_then(|end| values.get(&placeholder_text[1..end]));
+        if let Some(value) = value {
+            result.push_str(value);
+            remaining = &placeholder_text[end.unwrap() + 1..];
+        }
+        else {
+            result.push('{');
+            remaining = &placeholder_text[1..];
+        }
+    }
+    result.push_str(remaining);
+
+    return result.replace("\\n", "\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template_01() {
+        let mut values = BTreeMap::new();
+        values.insert("symbol", "BTC".to_string());
+        values.insert("price", "44,000.00".to_string());
+        values.insert("op", "<=".to_string());
+
+        assert_eq!(render_template("{symbol} {op} {price}", &values), "BTC <= 44,000.00");
+        assert_eq!(render_template("{symbol}: {unknown} {price", &values), "BTC: {unknown} {price");
+        assert_eq!(render_template("Alert\\n{{symbol}}", &values), "Alert\n{BTC}");
+    }
+}
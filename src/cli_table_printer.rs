@@ -98,6 +98,10 @@ impl CLITablePrinter {
         self.num_columns += 1;
     }
 
+    pub fn get_num_columns(&self) -> usize {
+        return self.num_columns;
+    }
+
     // this one starts from the beginning
     pub fn add_row_strings(&mut self, vals: &[&str]) {
         assert!(vals.len() == self.num_columns);
@@ -17,6 +17,11 @@ use std::io::{BufRead, BufReader};
 
 use std::collections::BTreeMap;
 
+use chrono::{FixedOffset};
+
+use crate::time_window::{TimeWindow, parse_timezone};
+use crate::alert_template::{AlertTemplate};
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ConfigSubType {
     None,
@@ -31,6 +36,29 @@ pub enum DisplayDataViewType {
     FullData,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DisplaySortType {
+    // the order the symbols were specified in 'wantedCoins'
+    WantedCoins,
+    Symbol,
+    Name,
+    // the below sort with the largest values first
+    Price,
+    PercentChange24h,
+}
+
+// what to do with provider alerts which trigger during quiet hours
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QuietHoursAction {
+    // don't send them, and don't start any sleep periods, so they can trigger again after quiet hours
+    Hold,
+    // send them (combined) once quiet hours have finished
+    Queue,
+    // use these actions instead of the provider
+    Print,
+    ShowNotification,
+}
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub display_config:     DisplayConfig,
@@ -54,7 +82,12 @@ pub struct DisplayConfig {
     //       converts that to seconds when reading the file.
     pub update_period:          u64,
 
+    // whether to align the updates to minute boundaries
+    pub align_to_minute:        bool,
+
     pub data_view_type:         DisplayDataViewType,
+
+    pub sort_type:              DisplaySortType,
 }
 
 #[derive(Clone, Debug)]
@@ -72,6 +105,13 @@ pub struct AlertConfig {
     //       converts that to seconds when reading the file.
     pub check_period:           u64,
 
+    // optional per-coin check periods in seconds, key = lowercase symbol. Alerts use the shortest of these
+    // (or the above for coins without one) for the coins in the rule, unless the alert has its own.
+    pub coin_check_periods:     BTreeMap<String, u64>,
+
+    // whether to align the checks to minute boundaries
+    pub align_to_minute:        bool,
+
     // time in seconds to not alert again after an initial alert, globally...
     pub global_sleep_period:    u64,
 
@@ -86,6 +126,54 @@ pub struct AlertConfig {
     // if action and providers are the same)
     pub combine_multiple_alerts:      bool,
 
+    // time in seconds to allow 'runCommand:' alert actions to run for before they're killed
+    pub command_timeout:        u64,
+
+    // time in seconds before the first retry of a provider alert which failed to send, which doubles for
+    // each further retry, and the time after the first attempt to give up retrying
+    pub retry_initial_delay:    u64,
+    pub retry_max_age:          u64,
+
+    // the timezone for quiet hours, per-alert active hours and digest times, None means local time
+    pub timezone:               Option<FixedOffset>,
+
+    // daily time window in which provider alerts aren't sent, but are instead handled by quiet_hours_action
+    pub quiet_hours:            Option<TimeWindow>,
+    pub quiet_hours_action:     QuietHoursAction,
+
+    // where to persist the state of alerts (i.e. sleep periods) across restarts, None to not do so
+    pub state_file_path:        Option<String>,
+
+    // where to log every alert decision (JSON lines), None to not do so
+    pub history_file_path:      Option<String>,
+
+    // time in seconds between escalations of alerts which haven't been acknowledged
+    pub escalation_period:      u64,
+    // where 'cryptmon alerts ack' writes acknowledgements of escalating alerts for the alert service to pick up
+    pub ack_file_path:          Option<String>,
+
+    // where to persist the monthly usage counts of alert providers, None to only track them while running
+    pub usage_file_path:        Option<String>,
+    // warn (and combine alerts for the provider) when a provider's remaining quota gets down to this
+    pub quota_warning_remaining: u32,
+
+    // where to record the prices fetched by the alert service (JSON lines) for backtesting, None to not do so
+    pub price_record_file_path: Option<String>,
+
+    // the actions to alert with when the price feed is down (and when it recovers), None to not do so,
+    // i.e. "[print, pushsafer|textbelt]"
+    pub feed_down_actions:      Option<String>,
+    // the feed is considered down after either this many consecutive failures to get the prices,
+    // or this many seconds of failures. 0 disables either.
+    pub feed_down_failures:     u32,
+    pub feed_down_period:       u64,
+
+    // the global subject/message templates for alerts. These can also be overridden per provider with the
+    // provider's 'subjectTemplate' and 'messageTemplate' params, and per alert with named templates.
+    pub template:               AlertTemplate,
+    // key = template name
+    pub named_templates:        BTreeMap<String, AlertTemplate>,
+
     pub alert_provider_configs: BTreeMap<String, AlertProviderConfig>,
 
     // for the moment, we'll do this, and defer actual processing of config strings
@@ -93,7 +181,20 @@ pub struct AlertConfig {
     // completely tightly-coupled together, although we may want to revisit this...
     // Note: this has already got a little messy, and the below only exist if the state
     // wasn't extracted first for 'alert_provider_configs' above...
-    pub alert_config_strings:   Vec<String>,
+    // scheduled digests of the watched coins' prices, i.e. "daily 09:00, textbelt"
+    pub digest_config_strings:  Vec<AlertConfigString>,
+
+    pub alert_config_strings:   Vec<AlertConfigString>,
+}
+
+// the raw string of an alert rule from the config file, along with where it came from for error reporting
+#[derive(Clone, Debug)]
+pub struct AlertConfigString {
+    pub text:           String,
+
+    // 1-based line number in the config file, and the 0-based column the text starts at in that line
+    pub line_number:    usize,
+    pub column_offset:  usize,
 }
 
 // I don't *really* like having this here, as it doesn't seem *completely* right, but I don't
@@ -123,32 +224,65 @@ impl AlertProviderConfig {
 
 impl Config {
     pub fn load() -> Config {
-        // set defaults
+        let mut config = Config::new_defaults();
+
+        if !config.load_config_file() {
+            // we didn't find a config file, so add some currency symbols as the default so we at least load something by default...
+            config.display_config.wanted_coins.push("BTC".to_string());
+            config.display_config.wanted_coins.push("ETH".to_string());
+            config.display_config.wanted_coins.push("SOL".to_string());
+            config.display_config.wanted_coins.push("LTC".to_string());
+        }
+
+        return config;
+    }
+
+    // the defaults, along with the config lines in the string, i.e. for tests
+    #[cfg(test)]
+    pub fn from_config_string(config_contents: &str) -> Config {
+        let mut config = Config::new_defaults();
+        config.load_config_lines(config_contents.as_bytes());
+        return config;
+    }
+
+    fn new_defaults() -> Config {
         let display_config = DisplayConfig {data_provider: "coingecko".to_string(), fiat_currency: "nzd".to_string(),
                              wanted_coins: Vec::with_capacity(0), coin_name_ignore_items: BTreeMap::new(),
-                             update_period: 120, data_view_type: DisplayDataViewType::MediumData };
+                             update_period: 120, align_to_minute: false, data_view_type: DisplayDataViewType::MediumData,
+                             sort_type: DisplaySortType::WantedCoins };
         
         let alert_config = AlertConfig {data_provider: "coingecko".to_string(), fiat_currency: "nzd".to_string(),
                                     coin_name_ignore_items: BTreeMap::new(), check_period: 120,
+                                    coin_check_periods: BTreeMap::new(),
+                                    align_to_minute: false,
                                     global_sleep_period: convert_time_period_string_to_seconds("1h").unwrap(),
                                     per_alert_sleep_period: convert_time_period_string_to_seconds("2h").unwrap(),
                                     watermark_trip_sleep_enabled: false,
                                     watermark_trip_sleep_period: convert_time_period_string_to_seconds("6h").unwrap(),
                                     combine_multiple_alerts: true,
+                                    command_timeout: 30,
+                                    retry_initial_delay: 60,
+                                    retry_max_age: convert_time_period_string_to_seconds("1h").unwrap(),
+                                    timezone: None,
+                                    quiet_hours: None,
+                                    quiet_hours_action: QuietHoursAction::Queue,
+                                    state_file_path: None,
+                                    history_file_path: None,
+                                    escalation_period: convert_time_period_string_to_seconds("30m").unwrap(),
+                                    ack_file_path: None,
+                                    usage_file_path: None,
+                                    quota_warning_remaining: 5,
+                                    price_record_file_path: None,
+                                    feed_down_actions: None,
+                                    feed_down_failures: 3,
+                                    feed_down_period: convert_time_period_string_to_seconds("15m").unwrap(),
+                                    template: AlertTemplate::default(),
+                                    named_templates: BTreeMap::new(),
                                     alert_provider_configs: BTreeMap::new(),
+                                    digest_config_strings: Vec::new(),
                                     alert_config_strings: Vec::with_capacity(0) };
         
-        let mut config = Config { display_config, alert_config };
-
-        if !config.load_config_file() {
-            // we didn't find a config file, so add some currency symbols as the default so we at least load something by default...
-            config.display_config.wanted_coins.push("BTC".to_string());
-            config.display_config.wanted_coins.push("ETH".to_string());
-            config.display_config.wanted_coins.push("SOL".to_string());
-            config.display_config.wanted_coins.push("LTC".to_string());
-        }
-
-        return config;
+        return Config { display_config, alert_config };
     }
 
     fn load_config_file(&mut self) -> bool {
@@ -188,15 +322,26 @@ impl Config {
             }
         }
 
-        let file = std::fs::File::open(config_path);
+        let file = std::fs::File::open(&config_path);
         if file.is_err() {
             eprintln!("Warning: Can't find a cryptmon.ini file for config, so using default configuration...");
             return false;
         }
 
-        let reader = BufReader::new(file.unwrap());
+        // by default, keep the alert state, history, acknowledgement and provider usage files next to the config file
+        let config_dir = std::path::Path::new(&config_path).parent().unwrap_or_else(|| std::path::Path::new("."));
+        self.alert_config.state_file_path = Some(config_dir.join("cryptmon_alert_state.json").to_string_lossy().to_string());
+        self.alert_config.history_file_path = Some(config_dir.join("cryptmon_alert_history.jsonl").to_string_lossy().to_string());
+        self.alert_config.ack_file_path = Some(config_dir.join("cryptmon_alert_acks.txt").to_string_lossy().to_string());
+        self.alert_config.usage_file_path = Some(config_dir.join("cryptmon_provider_usage.json").to_string_lossy().to_string());
+
+        self.load_config_lines(BufReader::new(file.unwrap()));
 
-        for line in reader.lines() {
+        return true;
+    }
+
+    fn load_config_lines<R: BufRead>(&mut self, reader: R) {
+        for (line_index, line) in reader.lines().enumerate() {
             let line = line.unwrap();
 
             // ignore empty lines and comments
@@ -254,6 +399,19 @@ impl Config {
                         _      =>      DisplayDataViewType::MediumData,
                     }
                 }
+                else if sub_type == ConfigSubType::Display && item_key == "sortType" {
+                    self.display_config.sort_type = match item_val {
+                        "wantedCoins" =>      DisplaySortType::WantedCoins,
+                        "symbol" =>           DisplaySortType::Symbol,
+                        "name" =>             DisplaySortType::Name,
+                        "price" =>            DisplaySortType::Price,
+                        "percentChange24h" => DisplaySortType::PercentChange24h,
+                        _      => {
+                            eprintln!("Error: Unknown 'display.sortType' value: '{}', using 'wantedCoins' instead.", item_val);
+                            DisplaySortType::WantedCoins
+                        }
+                    }
+                }
                 else if sub_type == ConfigSubType::Display && item_key == "updatePeriod" {
                     if let Some(period_in_secs) = convert_time_period_string_to_seconds(item_val) {
                         self.display_config.update_period = period_in_secs;
@@ -272,6 +430,24 @@ impl Config {
                         //       want to do it here, so that we can provide the name of the param item in the error...
                     }
                 }
+                else if sub_type == ConfigSubType::Display && item_key == "alignToMinute" {
+                    self.display_config.align_to_minute = item_val == "true" || item_val == "1";
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "coinCheckPeriods" {
+                    for pair in item_val.split(',') {
+                        if let Some((sym, period)) = pair.split_once('=').filter(|(_, period)| !period.trim().is_empty()) {
+                            if let Some(period_in_secs) = convert_time_period_string_to_seconds(period.trim()) {
+                                self.alert_config.coin_check_periods.insert(sym.trim().to_ascii_lowercase(), period_in_secs);
+                            }
+                        }
+                        else {
+                            eprintln!("Error: Invalid 'alerts.coinCheckPeriods' item: '{}', it should be in the form 'SYMBOL=period'", pair.trim());
+                        }
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "alignToMinute" {
+                    self.alert_config.align_to_minute = item_val == "true" || item_val == "1";
+                }
                 else if sub_type == ConfigSubType::Alerts && item_key == "globalSleepPeriod" {
                     if let Some(period_in_secs) = convert_time_period_string_to_seconds(item_val) {
                         self.alert_config.global_sleep_period = period_in_secs;
@@ -305,6 +481,144 @@ impl Config {
                 else if sub_type == ConfigSubType::Alerts && item_key == "combineMultipleAlerts" {
                     self.alert_config.combine_multiple_alerts = item_val == "true" || item_val == "1";
                 }
+                else if sub_type == ConfigSubType::Alerts && item_key == "commandTimeout" {
+                    if let Some(period_in_secs) = convert_time_period_string_to_seconds(item_val) {
+                        self.alert_config.command_timeout = period_in_secs;
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "retryInitialDelay" {
+                    if let Some(period_in_secs) = convert_time_period_string_to_seconds(item_val) {
+                        self.alert_config.retry_initial_delay = period_in_secs;
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "retryMaxAge" {
+                    if let Some(period_in_secs) = convert_time_period_string_to_seconds(item_val) {
+                        self.alert_config.retry_max_age = period_in_secs;
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "timezone" {
+                    if let Some(timezone) = parse_timezone(item_val) {
+                        self.alert_config.timezone = timezone;
+                    }
+                    else {
+                        eprintln!("Error: Invalid 'alerts.timezone' value: '{}', it should be 'local', 'UTC' or an offset like '+12:00'. Using local time instead.", item_val);
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "quietHours" {
+                    self.alert_config.quiet_hours = TimeWindow::parse(item_val);
+                    if self.alert_config.quiet_hours.is_none() {
+                        eprintln!("Error: Invalid 'alerts.quietHours' value: '{}', it should be in the form '23:00-07:00'.", item_val);
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "quietHoursAction" {
+                    self.alert_config.quiet_hours_action = match item_val {
+                        "hold" =>             QuietHoursAction::Hold,
+                        "queue" =>            QuietHoursAction::Queue,
+                        "print" =>            QuietHoursAction::Print,
+                        "showNotification" => QuietHoursAction::ShowNotification,
+                        _      => {
+                            eprintln!("Error: Unknown 'alerts.quietHoursAction' value: '{}', using 'queue' instead.", item_val);
+                            QuietHoursAction::Queue
+                        }
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "stateFile" {
+                    if item_val == "off" || item_val == "none" {
+                        self.alert_config.state_file_path = None;
+                    }
+                    else {
+                        self.alert_config.state_file_path = Some(item_val.to_string());
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "historyFile" {
+                    if item_val == "off" || item_val == "none" {
+                        self.alert_config.history_file_path = None;
+                    }
+                    else {
+                        self.alert_config.history_file_path = Some(item_val.to_string());
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "escalationPeriod" {
+                    if let Some(period_in_secs) = convert_time_period_string_to_seconds(item_val) {
+                        self.alert_config.escalation_period = period_in_secs;
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "ackFile" {
+                    if item_val == "off" || item_val == "none" {
+                        self.alert_config.ack_file_path = None;
+                    }
+                    else {
+                        self.alert_config.ack_file_path = Some(item_val.to_string());
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "usageFile" {
+                    if item_val == "off" || item_val == "none" {
+                        self.alert_config.usage_file_path = None;
+                    }
+                    else {
+                        self.alert_config.usage_file_path = Some(item_val.to_string());
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "quotaWarningRemaining" {
+                    if let Ok(remaining) = item_val.parse::<u32>() {
+                        self.alert_config.quota_warning_remaining = remaining;
+                    }
+                    else {
+                        eprintln!("Error: Invalid 'alerts.quotaWarningRemaining' value: '{}'.", item_val);
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "feedDownActions" {
+                    if item_val == "off" || item_val == "none" {
+                        self.alert_config.feed_down_actions = None;
+                    }
+                    else {
+                        self.alert_config.feed_down_actions = Some(item_val.to_string());
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "feedDownFailures" {
+                    if let Ok(failures) = item_val.parse::<u32>() {
+                        self.alert_config.feed_down_failures = failures;
+                    }
+                    else {
+                        eprintln!("Error: Invalid 'alerts.feedDownFailures' value: '{}'.", item_val);
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "feedDownPeriod" {
+                    if let Some(period_in_secs) = convert_time_period_string_to_seconds(item_val) {
+                        self.alert_config.feed_down_period = period_in_secs;
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "priceRecordFile" {
+                    if item_val == "off" || item_val == "none" {
+                        self.alert_config.price_record_file_path = None;
+                    }
+                    else {
+                        self.alert_config.price_record_file_path = Some(item_val.to_string());
+                    }
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "subjectTemplate" {
+                    self.alert_config.template.subject = Some(item_val.to_string());
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key == "messageTemplate" {
+                    self.alert_config.template.message = Some(item_val.to_string());
+                }
+                else if sub_type == ConfigSubType::Alerts && item_key.starts_with("template.") {
+                    // i.e. "template.terse.message"
+                    let template_key = item_key.strip_prefix("template.").unwrap();
+                    if let Some((template_name, template_type)) = template_key.rsplit_once('.') {
+                        let template = self.alert_config.named_templates.entry(template_name.to_string()).or_default();
+                        if template_type == "subject" {
+                            template.subject = Some(item_val.to_string());
+                            continue;
+                        }
+                        else if template_type == "message" {
+                            template.message = Some(item_val.to_string());
+                            continue;
+                        }
+                    }
+
+                    eprintln!("Error: Invalid alert template config: '{}', it should be 'alerts.template.<name>.subject' or 'alerts.template.<name>.message'.", item_key);
+                }
                 else if sub_type == ConfigSubType::Alerts && item_key.starts_with("provider.") {
                     if let Some(definition_key) = item_key.strip_prefix("provider.") {
                         if let Some(provider_name_end) = definition_key.find('.') {
@@ -334,16 +648,22 @@ impl Config {
 
                     eprintln!("Error processing alert provider config: {} - {}", item_key, item_val);
                 }
+                else if sub_type == ConfigSubType::Alerts && item_key == "newDigest" {
+                    let column_offset = line[..line.rfind(item_val).unwrap_or(0)].chars().count();
+                    self.alert_config.digest_config_strings.push(AlertConfigString { text: item_val.to_string(),
+                                                                                     line_number: line_index + 1, column_offset });
+                }
                 else if sub_type == ConfigSubType::Alerts && item_key == "newAlert" {
-                    self.alert_config.alert_config_strings.push(item_val.to_string());
+                    // the value is always at the end of the line, so rfind() will find the right one...
+                    let column_offset = line[..line.rfind(item_val).unwrap_or(0)].chars().count();
+                    self.alert_config.alert_config_strings.push(AlertConfigString { text: item_val.to_string(),
+                                                                                    line_number: line_index + 1, column_offset });
                 }
             }
             else {
                 eprintln!("Error: malformed line in cryptmon.ini, will be ignored.");
             }
         }
-
-        return true;
     }
 }
 
@@ -369,7 +689,7 @@ fn get_key_value_parts(str_val: &str) -> Option<(ConfigSubType, &str, &str)> {
 }
 
 // TODO: better error handling and reporting, we might need context as well for reporting, so result would be better...
-fn convert_time_period_string_to_seconds(str_val: &str) -> Option<u64> {
+pub fn convert_time_period_string_to_seconds(str_val: &str) -> Option<u64> {
     // TODO: there's probably a better way of doing this...
     let mut local_value = str_val.to_string();
     let last_char = str_val.chars().last().unwrap();
@@ -0,0 +1,110 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+
+use chrono::{DateTime, Duration, Local};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FeedHealthChange {
+    // the number of consecutive failures so far, and when they started
+    WentDown { failures: u32, since: DateTime<Local>, last_error: String },
+    // when the feed went down (or rather, started failing)
+    Recovered { since: DateTime<Local> },
+}
+
+// Keeps track of failures to get prices from the price provider, so we can alert when the feed is down
+// rather than silently not checking any alerts.
+pub struct FeedHealthMonitor {
+    // 0 disables either of these
+    failure_threshold:      u32,
+    // in seconds
+    down_period:            u64,
+
+    consecutive_failures:   u32,
+    first_failure_time:     Option<DateTime<Local>>,
+
+    is_down:                bool,
+}
+
+impl FeedHealthMonitor {
+    pub fn new(failure_threshold: u32, down_period: u64) -> FeedHealthMonitor {
+        return FeedHealthMonitor { failure_threshold, down_period, consecutive_failures: 0, first_failure_time: None, is_down: false };
+    }
+
+    // returns a change if the feed is now considered down
+    pub fn record_failure(&mut self, error: &str, time: DateTime<Local>) -> Option<FeedHealthChange> {
+        self.consecutive_failures += 1;
+        // Note: the time without data is from the first failure, rather than the last prices, so that not fetching
+        //       prices during the global sleep doesn't count
+        let first_failure_time = *self.first_failure_time.get_or_insert(time);
+
+        if self.is_down {
+            return None;
+        }
+
+        let too_many_failures = self.failure_threshold > 0 && self.consecutive_failures >= self.failure_threshold;
+        let down_for_too_long = self.down_period > 0 && time - first_failure_time >= Duration::seconds(self.down_period as i64);
+        if !too_many_failures && !down_for_too_long {
+            return None;
+        }
+
+        self.is_down = true;
+        return Some(FeedHealthChange::WentDown { failures: self.consecutive_failures, since: first_failure_time, last_error: error.to_string() });
+    }
+
+    // returns a change if the feed was considered down
+    pub fn record_success(&mut self) -> Option<FeedHealthChange> {
+        let first_failure_time = self.first_failure_time.take();
+        self.consecutive_failures = 0;
+
+        if !self.is_down {
+            return None;
+        }
+
+        self.is_down = false;
+        return first_failure_time.map(|since| FeedHealthChange::Recovered { since });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_health_monitor_01() {
+        let start_time = Local::now();
+        let minutes = |m: i64| start_time + Duration::minutes(m);
+
+        let mut monitor = FeedHealthMonitor::new(3, 15 * 60);
+        assert_eq!(monitor.record_failure("timeout", minutes(0)), None);
+        assert_eq!(monitor.record_failure("timeout", minutes(2)), None);
+        // a success resets the count
+        assert_eq!(monitor.record_success(), None);
+        assert_eq!(monitor.record_failure("timeout", minutes(6)), None);
+        assert_eq!(monitor.record_failure("timeout", minutes(8)), None);
+        assert_eq!(monitor.record_failure("HTTP 500", minutes(10)),
+                   Some(FeedHealthChange::WentDown { failures: 3, since: minutes(6), last_error: "HTTP 500".to_string() }));
+        // only once while it's down
+        assert_eq!(monitor.record_failure("timeout", minutes(12)), None);
+        assert_eq!(monitor.record_success(), Some(FeedHealthChange::Recovered { since: minutes(6) }));
+
+        // or after the period, with the failure count disabled
+        let mut monitor = FeedHealthMonitor::new(0, 15 * 60);
+        for m in 0..15 {
+            assert_eq!(monitor.record_failure("timeout", minutes(m)), None);
+        }
+        assert!(matches!(monitor.record_failure("timeout", minutes(15)), Some(FeedHealthChange::WentDown { failures: 16, .. })));
+    }
+}
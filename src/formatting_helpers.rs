@@ -43,6 +43,20 @@ pub fn smart_format(val: f64) -> String {
 }
 
 
+// formats a time period in seconds to the most compact unit it can be exactly represented in,
+// i.e. the same form as config file time periods
+// Note: u64::is_multiple_of() needs Rust 1.87, so this uses '%'
+#[allow(clippy::manual_is_multiple_of)]
+pub fn format_time_period(seconds: u64) -> String {
+    if seconds > 0 && seconds % (60 * 60) == 0 {
+        return format!("{}h", seconds / (60 * 60));
+    }
+    else if seconds > 0 && seconds % 60 == 0 {
+        return format!("{}m", seconds / 60);
+    }
+
+    return format!("{}s", seconds);
+}
 
 #[cfg(test)]
 mod tests {
@@ -15,6 +15,14 @@
 
 mod config;
 mod alert_provider;
+mod alert_expression;
+mod alert_command;
+mod alert_state_file;
+mod alert_history;
+mod alert_template;
+mod alert_ack;
+mod alert_quota;
+mod alert_backtest;
 
 #[cfg(feature = "smtp")]
 mod alert_provider_smtp_mail;
@@ -23,6 +31,13 @@ mod alert_provider_pushsafer;
 mod alert_provider_simplepush;
 mod alert_provider_textbelt;
 mod alert_service;
+mod price_history;
+mod price_recording;
+mod time_window;
+mod clock;
+mod scheduler;
+mod feed_health;
+mod alert_digest;
 
 mod price_provider;
 mod price_provider_coingecko;
@@ -30,24 +45,30 @@ mod price_provider_coinmarketcap;
 mod price_provider_cryptocompare;
 mod cli_table_printer;
 mod price_view_terminal;
+mod provider_check;
 
 mod formatting_helpers;
 
 use config::{Config};
 
 use alert_service::{AlertService};
-use price_provider::{PriceProvider, ConfigDetails, PriceProviderParams};
-use price_provider_coingecko::{ProviderCoinGecko};
-use price_provider_coinmarketcap::{ProviderCoinMarketCap};
-use price_provider_cryptocompare::{ProviderCryptoCompare};
+use alert_history::{run_alerts_history};
+use alert_ack::{run_alerts_ack};
+use alert_backtest::{run_alerts_backtest};
+use price_provider::{PriceProvider, ConfigDetails, PriceProviderParams, PRICE_PROVIDER_NAMES, create_price_provider};
 use price_view_terminal::PriceViewTerminal;
+use provider_check::{run_providers_check};
 
 use std::env;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 enum RunType {
     View,
-    Alerts
+    Alerts,
+    AlertsHistory,
+    AlertsAck,
+    AlertsBacktest,
+    ProvidersCheck
 }
 
 fn main() {
@@ -63,8 +84,45 @@ fn main() {
     if args.len() > 1 {
         let first_arg = &args[1];
         if first_arg == "alerts" {
-            run_type = RunType::Alerts;
+            if args.len() > 2 && args[2] == "history" {
+                run_type = RunType::AlertsHistory;
+            }
+            else if args.len() > 2 && args[2] == "ack" {
+                run_type = RunType::AlertsAck;
+            }
+            else if args.len() > 2 && args[2] == "backtest" {
+                run_type = RunType::AlertsBacktest;
+            }
+            else {
+                run_type = RunType::Alerts;
+            }
         }
+        else if first_arg == "providers" {
+            if args.len() > 2 && args[2] == "check" {
+                run_type = RunType::ProvidersCheck;
+            }
+            else {
+                eprintln!("Error: Unknown 'providers' command. Usage: cryptmon providers check");
+                return;
+            }
+        }
+    }
+
+    if run_type == RunType::ProvidersCheck {
+        run_providers_check(&config);
+        return;
+    }
+    else if run_type == RunType::AlertsHistory {
+        run_alerts_history(&config, &args[3..]);
+        return;
+    }
+    else if run_type == RunType::AlertsAck {
+        run_alerts_ack(&config, &args[3..]);
+        return;
+    }
+    else if run_type == RunType::AlertsBacktest {
+        run_alerts_backtest(&config, &args[3..]);
+        return;
     }
 
     // TODO: this whole chicken-and-egg situation with PriceProvider/Config/PriceProviderParams is a mess...
@@ -79,30 +137,16 @@ fn main() {
     provider_params.fiat_currency = fiat_currency.clone();
     provider_params.coin_name_ignore_items = coin_name_ignore_items.clone();
 
-    // TODO: abstract this away somewhere so it's A: encapsuled, and B: re-useable?
-    if data_provider == "coingecko" {
-        if let Some((prov, config_dets)) = ProviderCoinGecko::new_from_config(&provider_params) {
-            provider = Some(Box::new(prov));
-            config_details = config_dets;
-        }
-    }
-    else if data_provider == "coinmarketcap" {
-        if let Some((prov, config_dets)) = ProviderCoinMarketCap::new_from_config(&provider_params) {
-            provider = Some(Box::new(prov));
-            config_details = config_dets;
-        }
-    }
-    else if data_provider == "cryptocompare" {
-        if let Some((prov, config_dets)) = ProviderCryptoCompare::new_from_config(&provider_params) {
-            provider = Some(Box::new(prov));
-            config_details = config_dets;
-        }
-    }
-    else {
+    if !PRICE_PROVIDER_NAMES.contains(&data_provider.as_str()) {
         eprintln!("Error: Unknown 'dataProvider' config item specified: {}. Please make sure it is one of the supported price providers.", data_provider);
         return;
     }
 
+    if let Some((prov, config_dets)) = create_price_provider(data_provider, &provider_params) {
+        provider = Some(prov);
+        config_details = config_dets;
+    }
+
     if provider.is_none() {
         eprintln!("Error: Couldn't create required PriceProvider item to obtain coin currency values with. cryptmon will exit.");
         return;
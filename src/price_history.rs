@@ -0,0 +1,129 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use crate::price_provider::{CoinPriceItem};
+
+use std::collections::{BTreeMap, VecDeque};
+
+use chrono::{DateTime, Local, Duration};
+
+// In-memory history of the prices we've polled for each coin, so that we can work out moves over
+// arbitrary time windows, independently of what the price provider gives us.
+pub struct PriceHistory {
+    // samples older than this (in seconds) are discarded
+    max_age:        u64,
+
+    // key = lowercase symbol, val: (time, price) samples, oldest first
+    coin_samples:   BTreeMap<String, VecDeque<(DateTime<Local>, f64)>>,
+}
+
+impl PriceHistory {
+    pub fn new(max_age: u64) -> PriceHistory {
+        return PriceHistory { max_age, coin_samples: BTreeMap::new() };
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        return self.max_age > 0;
+    }
+
+    pub fn add_prices(&mut self, prices: &[CoinPriceItem], time: DateTime<Local>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let cutoff_time = time - Duration::seconds(self.max_age as i64);
+
+        for price in prices {
+            let samples = self.coin_samples.entry(price.symbol.to_ascii_lowercase()).or_default();
+            samples.push_back((time, price.current_price));
+
+            while let Some((sample_time, _)) = samples.front() {
+                if *sample_time >= cutoff_time {
+                    break;
+                }
+                samples.pop_front();
+            }
+        }
+    }
+
+    // Returns the largest percentage move (signed) of the current (latest) price compared to any of the
+    // prices within the window (in seconds) before it, or None if there aren't enough samples yet.
+    pub fn get_largest_percent_move(&self, symbol: &str, window: u64, time: DateTime<Local>) -> Option<f64> {
+        let samples = self.coin_samples.get(symbol)?;
+        let (_, latest_price) = samples.back()?;
+
+        let window_start = time - Duration::seconds(window as i64);
+
+        let mut largest_move: Option<f64> = None;
+        // skip the latest one, as that's what we're comparing against...
+        for (sample_time, sample_price) in samples.iter().rev().skip(1) {
+            if *sample_time < window_start {
+                break;
+            }
+            if *sample_price == 0.0 {
+                continue;
+            }
+
+            let percent_move = (latest_price - sample_price) / sample_price * 100.0;
+            if largest_move.is_none() || percent_move.abs() > largest_move.unwrap().abs() {
+                largest_move = Some(percent_move);
+            }
+        }
+
+        return largest_move;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_largest_percent_move_01() {
+        let mut history = PriceHistory::new(60 * 60);
+        let start_time = Local::now();
+
+        // up 5%, then down to 2% below the start
+        let prices = [100.0, 105.0, 98.0];
+        for (count, price) in prices.iter().enumerate() {
+            history.add_prices(&[CoinPriceItem::new_basic("BTC", "BTC", *price)], start_time + Duration::minutes(count as i64 * 5));
+        }
+
+        let latest_time = start_time + Duration::minutes(10);
+
+        // within 5 mins, only the 105 -> 98 move counts
+        let move_5m = history.get_largest_percent_move("btc", 5 * 60, latest_time).unwrap();
+        assert!((move_5m - (-6.666)).abs() < 0.01);
+
+        // the 105 -> 98 move is still the largest over the full window
+        let move_15m = history.get_largest_percent_move("btc", 15 * 60, latest_time).unwrap();
+        assert!((move_15m - (-6.666)).abs() < 0.01);
+
+        assert!(history.get_largest_percent_move("eth", 15 * 60, latest_time).is_none());
+    }
+
+    #[test]
+    fn test_samples_pruned_01() {
+        let mut history = PriceHistory::new(10 * 60);
+        let start_time = Local::now();
+
+        history.add_prices(&[CoinPriceItem::new_basic("BTC", "BTC", 100.0)], start_time);
+        history.add_prices(&[CoinPriceItem::new_basic("BTC", "BTC", 200.0)], start_time + Duration::minutes(20));
+
+        // the first sample is too old to be kept, so there's nothing to compare against
+        let latest_time = start_time + Duration::minutes(20);
+        assert!(history.get_largest_percent_move("btc", 60 * 60, latest_time).is_none());
+    }
+}
@@ -16,6 +16,13 @@
 use std::collections::BTreeMap;
 use std::fmt;
 
+use crate::price_provider_coingecko::{ProviderCoinGecko};
+use crate::price_provider_coinmarketcap::{ProviderCoinMarketCap};
+use crate::price_provider_cryptocompare::{ProviderCryptoCompare};
+
+// names of all the price providers which can be specified with the 'dataProvider' config item
+pub const PRICE_PROVIDER_NAMES: [&str; 3] = ["coingecko", "coinmarketcap", "cryptocompare"];
+
 #[derive(Clone, Debug)]
 pub struct PriceProviderParams {
     pub fiat_currency:          String,
@@ -42,11 +49,24 @@ pub struct ConfigDetails {
     pub have_watermarks_24h:        bool,
 
     pub have_price_change_24h:      bool,
+
+    // wanted symbols (lowercase) which the provider couldn't resolve to a coin it knows about
+    // at configure() time, so which won't have prices returned for them.
+    pub unresolved_symbols:         Vec<String>,
 }
 
 impl ConfigDetails {
     pub fn new() -> ConfigDetails {
-        ConfigDetails { have_percent_change_1h: false, have_watermarks_24h: true, have_price_change_24h: true }
+        ConfigDetails { have_percent_change_1h: false, have_watermarks_24h: true, have_price_change_24h: true,
+                        unresolved_symbols: Vec::with_capacity(0) }
+    }
+
+    pub fn report_unresolved_symbols(&self) {
+        if !self.unresolved_symbols.is_empty() {
+            let symbols: Vec<String> = self.unresolved_symbols.iter().map(|s| s.to_ascii_uppercase()).collect();
+            eprintln!("Warning: The price provider could not resolve the following coin symbols, so no prices will be available for them: {}",
+                        symbols.join(", "));
+        }
     }
 }
 
@@ -64,6 +84,18 @@ pub struct CoinPriceItem {
     pub price_change_24h: f64,
     pub percent_change_1h: Option<f64>,
     pub percent_change_24h: f64,
+
+    // 24h trading volume, in the fiat currency
+    pub volume_24h:     Option<f64>,
+}
+
+impl CoinPriceItem {
+    // for tests, with just the price set, which other fields can be added to with '..CoinPriceItem::new_basic(...)'
+    #[cfg(test)]
+    pub fn new_basic(symbol: &str, name: &str, current_price: f64) -> CoinPriceItem {
+        return CoinPriceItem { symbol: symbol.to_string(), name: name.to_string(), current_price, watermarks_24h: None,
+                               price_change_24h: 0.0, percent_change_1h: None, percent_change_24h: 0.0, volume_24h: None };
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -89,6 +121,7 @@ pub enum GetDataError {
     InvalidAPIParams(String),
     ParseError(String),
     EmptyResults,
+    OtherError(String),
     NotImplemented
 }
 
@@ -103,6 +136,7 @@ impl fmt::Display for GetDataError {
             GetDataError::InvalidAPIParams(ref err) => write!(f, "Invalid API params provided: {}", err),
             GetDataError::ParseError(ref err) => write!(f, "Error parsing response: {}", err),
             GetDataError::EmptyResults => write!(f, "Empty results"),
+            GetDataError::OtherError(ref err) => write!(f, "Other error: {}", err),
             GetDataError::NotImplemented => write!(f, "Not implemented"),
         }
     }
@@ -121,3 +155,118 @@ pub trait PriceProvider {
     }
 
 }
+
+// Creates and configures the named price provider, returning None if either the name is unknown
+// or the provider couldn't be configured.
+pub fn create_price_provider(name: &str, params: &PriceProviderParams) -> Option<(Box<dyn PriceProvider>, ConfigDetails)> {
+    if name == "coingecko" {
+        if let Some((prov, config_dets)) = ProviderCoinGecko::new_from_config(params) {
+            return Some((Box::new(prov), config_dets));
+        }
+    }
+    else if name == "coinmarketcap" {
+        if let Some((prov, config_dets)) = ProviderCoinMarketCap::new_from_config(params) {
+            return Some((Box::new(prov), config_dets));
+        }
+    }
+    else if name == "cryptocompare" {
+        if let Some((prov, config_dets)) = ProviderCryptoCompare::new_from_config(params) {
+            return Some((Box::new(prov), config_dets));
+        }
+    }
+
+    return None;
+}
+
+// maximum number of batch requests to have in-flight at once, so we don't hammer APIs with large watchlists
+const MAX_CONCURRENT_BATCH_REQUESTS: usize = 4;
+
+// Helper for providers to split large sets of wanted items (ids or symbols) into multiple smaller requests,
+// as the APIs have URL length and parameter count limits.
+// Batches are requested concurrently and the results merged in batch order. If only some of the batches
+// fail, the results of the successful ones are still returned (with the failures printed), and an error
+// is only returned if all of them failed.
+pub fn get_prices_batched<F>(items: &[String], batch_size: usize, get_batch_prices: F) -> Result<Vec<CoinPriceItem>, GetDataError>
+where
+    F: Fn(&[String]) -> Result<Vec<CoinPriceItem>, GetDataError> + Sync
+{
+    let batches: Vec<&[String]> = items.chunks(batch_size.max(1)).collect();
+
+    // fast path for the common case, where there's no need for threads...
+    if batches.len() == 1 {
+        return get_batch_prices(batches[0]);
+    }
+
+    let mut batch_results = Vec::with_capacity(batches.len());
+    for batch_group in batches.chunks(MAX_CONCURRENT_BATCH_REQUESTS) {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = batch_group.iter().map(|batch| {
+                let get_batch_prices = &get_batch_prices;
+                scope.spawn(move || get_batch_prices(batch))
+            }).collect();
+
+            for handle in handles {
+                let result = handle.join().unwrap_or_else(|_| Err(GetDataError::OtherError("Batch request thread panicked".to_string())));
+                batch_results.push(result);
+            }
+        });
+    }
+
+    let mut results = Vec::with_capacity(items.len());
+    let mut last_error = None;
+    let mut num_failed_batches = 0;
+
+    for result in batch_results {
+        match result {
+            Ok(mut prices) => results.append(&mut prices),
+            Err(err) => {
+                num_failed_batches += 1;
+                last_error = Some(err);
+            }
+        }
+    }
+
+    if let Some(err) = last_error {
+        if results.is_empty() {
+            return Err(err);
+        }
+
+        eprintln!("Warning: {} of {} batched price requests failed, so some prices will be missing. Last error: {}",
+                    num_failed_batches, batches.len(), err);
+    }
+
+    return Ok(results);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_get_prices_batched_01() {
+        let items: Vec<String> = (0..25).map(|i| format!("c{}", i)).collect();
+        let batch_sizes = Mutex::new(Vec::new());
+
+        let get_batch_prices = |batch: &[String]| -> Result<Vec<CoinPriceItem>, GetDataError> {
+            batch_sizes.lock().unwrap().push(batch.len());
+            if batch.contains(&"c12".to_string()) {
+                return Err(GetDataError::ErrorResponse("batch failed".to_string()));
+            }
+            return Ok(batch.iter().map(|symbol| CoinPriceItem::new_basic(symbol, symbol, 1.0)).collect());
+        };
+
+        // the results are in batch order, and the failed batch is just missing
+        let results = get_prices_batched(&items, 10, get_batch_prices).unwrap();
+        let mut sizes = batch_sizes.lock().unwrap().clone();
+        sizes.sort();
+        assert_eq!(sizes, vec![5, 10, 10]);
+        let expected: Vec<String> = (0..10).chain(20..25).map(|i| format!("c{}", i)).collect();
+        assert_eq!(results.iter().map(|p| p.symbol.clone()).collect::<Vec<String>>(), expected);
+
+        // an error is only returned if all of them failed
+        let result = get_prices_batched(&items[10..15], 2, |_| Err(GetDataError::ErrorResponse("down".to_string())));
+        assert!(matches!(result, Err(GetDataError::ErrorResponse(_))));
+    }
+}
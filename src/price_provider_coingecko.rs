@@ -18,6 +18,11 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 use crate::price_provider::{PriceProvider, PriceProviderParams, ConfigDetails, GetDataError, CoinPriceItem, Watermarks};
+use crate::price_provider::{get_prices_batched};
+
+// the markets API returns at most 250 items per page, and long URLs get rejected,
+// so larger watchlists need to be split into multiple requests.
+const MAX_IDS_PER_REQUEST: usize = 100;
 
 // for results back from CoinGecko's API regarding the list of coins and their IDs
 //
@@ -49,6 +54,8 @@ struct CoinMarketPriceItem {
 
     price_change_24h:   f64,
     price_change_percentage_24h:    f64,
+
+    total_volume:       Option<f64>,
 }
 
 pub struct ProviderCoinGecko {
@@ -153,11 +160,19 @@ impl PriceProvider for ProviderCoinGecko {
             index += 1;
         }
 
+        let mut config_details = ConfigDetails::new();
+
+        // we might be being re-configured, so reset this...
+        self.ids_wanted.clear();
+
         for coin in &self.params.wanted_coin_symbols {
             if let Some(index) = lookup.get(&coin.to_ascii_uppercase()) {
                 let item = &self.full_coin_list[*index];
                 self.ids_wanted.push(item.id.clone());
             }
+            else {
+                config_details.unresolved_symbols.push(coin.to_ascii_lowercase());
+            }
         }
 
         self.currency_val = params.fiat_currency.to_ascii_lowercase();
@@ -166,7 +181,7 @@ impl PriceProvider for ProviderCoinGecko {
             self.currency_val = "nzd".to_string();
         }
 
-        return Some(ConfigDetails::new());
+        return Some(config_details);
     }
 
     fn get_current_prices(&self) -> Result<Vec<CoinPriceItem>, GetDataError> {
@@ -174,10 +189,17 @@ impl PriceProvider for ProviderCoinGecko {
             return Err(GetDataError::ConfigError("No currency symbols configured/requested".to_string()));
         }
 
-        let ids_param = self.ids_wanted.join(",");
+        return get_prices_batched(&self.ids_wanted, MAX_IDS_PER_REQUEST, |ids| self.get_prices_for_ids(ids));
+    }
+}
+
+impl ProviderCoinGecko {
+    fn get_prices_for_ids(&self, ids: &[String]) -> Result<Vec<CoinPriceItem>, GetDataError> {
+        let ids_param = ids.join(",");
 
-        let request_url = format!("https://api.coingecko.com/api/v3/coins/markets?vs_currency={}&ids={}",
-                                    self.currency_val, ids_param);
+        // Note: per_page needs to be specified, as the default of 100 would otherwise silently truncate the results
+        let request_url = format!("https://api.coingecko.com/api/v3/coins/markets?vs_currency={}&ids={}&per_page={}",
+                                    self.currency_val, ids_param, MAX_IDS_PER_REQUEST);
         
         let price_results = ureq::get(&request_url).call();
         if price_results.is_err() {
@@ -206,7 +228,8 @@ impl PriceProvider for ProviderCoinGecko {
                                         watermarks_24h: Some(Watermarks::new(src_res.low_24h, src_res.high_24h)),
                                         price_change_24h: src_res.price_change_24h,
                                         percent_change_1h: None,
-                                        percent_change_24h: src_res.price_change_percentage_24h };
+                                        percent_change_24h: src_res.price_change_percentage_24h,
+                                        volume_24h: src_res.total_volume };
 
             results.push(new_val);
         }
@@ -18,6 +18,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 use crate::price_provider::{PriceProvider, PriceProviderParams, ConfigDetails, GetDataError, CoinPriceItem};
+use crate::price_provider::{get_prices_batched};
+
+// keep the request URLs to a sensible length for larger watchlists
+const MAX_SYMBOLS_PER_REQUEST: usize = 100;
 
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
@@ -95,7 +99,13 @@ impl PriceProvider for ProviderCoinMarketCap {
             return Err(GetDataError::ConfigError("No coin currency symbols configured/requested".to_string()));
         }
 
-        let symbol_param = self.params.wanted_coin_symbols.join(",");
+        return get_prices_batched(&self.params.wanted_coin_symbols, MAX_SYMBOLS_PER_REQUEST, |symbols| self.get_prices_for_symbols(symbols));
+    }
+}
+
+impl ProviderCoinMarketCap {
+    fn get_prices_for_symbols(&self, symbols: &[String]) -> Result<Vec<CoinPriceItem>, GetDataError> {
+        let symbol_param = symbols.join(",");
         let currency = self.params.fiat_currency.clone();
 
         let request_url = format!("https://pro-api.coinmarketcap.com/v1/cryptocurrency/quotes/latest?convert={}&symbol={}",
@@ -124,7 +134,7 @@ impl PriceProvider for ProviderCoinMarketCap {
 
         let mut results = Vec::with_capacity(coin_price_results.data.len());
 
-        for coin_symbol in &self.params.wanted_coin_symbols {
+        for coin_symbol in symbols {
             if let Some(coin_item) = coin_price_results.data.get(&coin_symbol.to_ascii_uppercase()) {
                 if let Some(currency_item) = coin_item.quote.get(&self.params.fiat_currency.to_ascii_uppercase()) {
 
@@ -133,7 +143,8 @@ impl PriceProvider for ProviderCoinMarketCap {
                         watermarks_24h: None,
                         price_change_24h: 0.0,
                         percent_change_1h: Some(currency_item.percent_change_1h),
-                        percent_change_24h: currency_item.percent_change_24h };
+                        percent_change_24h: currency_item.percent_change_24h,
+                        volume_24h: Some(currency_item.volume_24h) };
 
                     results.push(new_val);
                 }
@@ -21,8 +21,13 @@ use std::collections::BTreeSet;
 
 use crate::price_provider::Watermarks;
 use crate::price_provider::{PriceProvider, PriceProviderParams, ConfigDetails, GetDataError, CoinPriceItem};
+use crate::price_provider::{get_prices_batched};
 use crate::price_provider_coingecko;
 
+// the pricemultifull API has a limit of 300 chars for the 'fsyms' param, so larger watchlists
+// need to be split into multiple requests.
+const MAX_SYMBOLS_PER_REQUEST: usize = 50;
+
 // Note: the https://min-api.cryptocompare.com/data/pricemultifull API seems to very often
 //       (but not always!!?) return incorrect (out of date?) price results (and other values, like min/max)
 //       but the values do update very regularly, so not really sure what's going on...
@@ -61,6 +66,11 @@ struct CoinPriceResultItem {
     change_hour:                f64,
     #[serde(rename = "CHANGEPCTHOUR")]
     change_pct_hour:            f64,
+
+    // this is the volume in the 'to' (fiat) currency. It's optional, so it being missing for a coin
+    // doesn't break getting the prices
+    #[serde(rename = "VOLUME24HOURTO", default)]
+    volume_24_hour_to:          Option<f64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -181,6 +191,9 @@ impl PriceProvider for ProviderCryptoCompare {
 
         // for name lookup later...
         let mut wanted_coins = BTreeSet::new();
+
+        // we might be being re-configured, so reset this...
+        self.symbols_wanted.clear();
         
         for coin in &self.params.wanted_coin_symbols {
             self.symbols_wanted.push(coin.to_ascii_lowercase());
@@ -196,10 +209,20 @@ impl PriceProvider for ProviderCryptoCompare {
             self.currency_val = "nzd".to_string();
         }
 
+        let mut config_details = ConfigDetails::new();
+
         // use the CoinGecko one as it's much faster...
-        self.build_coin_name_lookup_coingecko(&wanted_coins);
+        if self.build_coin_name_lookup_coingecko(&wanted_coins) {
+            // Note: this is only an approximation given the lookup comes from a different provider,
+            //       but it's better than nothing for catching typos in symbols...
+            for coin in &self.symbols_wanted {
+                if !self.name_lookup.contains_key(&coin.to_ascii_uppercase()) {
+                    config_details.unresolved_symbols.push(coin.clone());
+                }
+            }
+        }
 
-        return Some(ConfigDetails::new());
+        return Some(config_details);
     }
 
     fn get_current_prices(&self) -> Result<Vec<CoinPriceItem>, GetDataError> {
@@ -207,7 +230,13 @@ impl PriceProvider for ProviderCryptoCompare {
             return Err(GetDataError::ConfigError("No currency symbols configured/requested".to_string()));
         }
 
-        let fsyms_param = self.symbols_wanted.join(",");
+        return get_prices_batched(&self.symbols_wanted, MAX_SYMBOLS_PER_REQUEST, |symbols| self.get_prices_for_symbols(symbols));
+    }
+}
+
+impl ProviderCryptoCompare {
+    fn get_prices_for_symbols(&self, symbols: &[String]) -> Result<Vec<CoinPriceItem>, GetDataError> {
+        let fsyms_param = symbols.join(",");
 
         let request_url = format!("https://min-api.cryptocompare.com/data/pricemultifull?fsyms={}&tsyms={}",
                                     fsyms_param, self.currency_val);
@@ -262,7 +291,8 @@ impl PriceProvider for ProviderCryptoCompare {
                                         watermarks_24h: Some(Watermarks::new(result_item.low_24_hour, result_item.high_24_hour)),
                                         price_change_24h: result_item.change_24_hour,
                                         percent_change_1h: None,
-                                        percent_change_24h: result_item.change_pct_24_hour };
+                                        percent_change_24h: result_item.change_pct_24_hour,
+                                        volume_24h: result_item.volume_24_hour_to };
                         
                         results.push(new_val);
                     }
@@ -0,0 +1,184 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use crate::price_provider::{PriceProvider, PriceProviderParams, ConfigDetails, CoinPriceItem, Watermarks};
+
+use serde::{Deserialize, Serialize};
+
+use std::io::{BufRead, BufReader, Write};
+
+use chrono::{DateTime, Local};
+
+// the prices at each recorded time, in time order
+pub type PriceRecords = Vec<(DateTime<Local>, Vec<CoinPriceItem>)>;
+
+// the serialisable version of CoinPriceItem
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedCoinPrice {
+    symbol:             String,
+    name:               String,
+    price:              f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    low24h:             Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    high24h:            Option<f64>,
+    price_change24h:    f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    percent_change1h:   Option<f64>,
+    percent_change24h:  f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    volume24h:          Option<f64>,
+}
+
+impl RecordedCoinPrice {
+    fn from_coin_item(item: &CoinPriceItem) -> RecordedCoinPrice {
+        return RecordedCoinPrice { symbol: item.symbol.clone(), name: item.name.clone(), price: item.current_price,
+                                   low24h: item.watermarks_24h.as_ref().map(|w| w.low), high24h: item.watermarks_24h.as_ref().map(|w| w.high),
+                                   price_change24h: item.price_change_24h, percent_change1h: item.percent_change_1h,
+                                   percent_change24h: item.percent_change_24h, volume24h: item.volume_24h };
+    }
+
+    fn to_coin_item(&self) -> CoinPriceItem {
+        let watermarks_24h = match (self.low24h, self.high24h) {
+            (Some(low), Some(high)) => Some(Watermarks::new(low, high)),
+            _ =>                       None,
+        };
+        return CoinPriceItem { symbol: self.symbol.clone(), name: self.name.clone(), current_price: self.price, watermarks_24h,
+                               price_change_24h: self.price_change24h, percent_change_1h: self.percent_change1h,
+                               percent_change_24h: self.percent_change24h, volume_24h: self.volume24h };
+    }
+}
+
+// a single line in the price record file
+#[derive(Serialize, Deserialize)]
+struct PriceRecord {
+    // RFC 3339
+    timestamp:  String,
+    prices:     Vec<RecordedCoinPrice>,
+}
+
+// Appends the prices fetched by the alert service to the price record file (if enabled) as JSON lines,
+// so that they can be replayed with 'cryptmon alerts backtest'.
+pub struct PriceRecorder {
+    path:       Option<String>,
+}
+
+impl PriceRecorder {
+    pub fn new(path: Option<String>) -> PriceRecorder {
+        return PriceRecorder { path };
+    }
+
+    pub fn record(&self, prices: &[CoinPriceItem], time: DateTime<Local>) {
+        if self.path.is_none() {
+            return;
+        }
+        let path = self.path.as_ref().unwrap();
+
+        let record = PriceRecord { timestamp: time.to_rfc3339(), prices: prices.iter().map(RecordedCoinPrice::from_coin_item).collect() };
+        let line = serde_json::to_string(&record).unwrap();
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path);
+        let res = file.and_then(|mut f| writeln!(f, "{}", line));
+        if let Err(err) = res {
+            eprintln!("Error: Couldn't write to price record file: '{}'. Error: {}", path, err);
+        }
+    }
+}
+
+// Loads the recorded prices within the time range (start inclusive, end exclusive), in time order.
+pub fn load_price_records(path: &str, start_time: DateTime<Local>, end_time: DateTime<Local>) -> Result<PriceRecords, String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+
+    let mut records = Vec::new();
+    for (line_index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.unwrap_or_default();
+        if line.is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str::<PriceRecord>(&line);
+        if record.is_err() {
+            eprintln!("Warning: Ignoring invalid line {} in price record file.", line_index + 1);
+            continue;
+        }
+        let record = record.unwrap();
+
+        let time = DateTime::parse_from_rfc3339(&record.timestamp);
+        if time.is_err() {
+            eprintln!("Warning: Ignoring line {} with an invalid timestamp in price record file.", line_index + 1);
+            continue;
+        }
+        let time = time.unwrap().with_timezone(&Local);
+
+        if time >= start_time && time < end_time {
+            records.push((time, record.prices.iter().map(|p| p.to_coin_item()).collect()));
+        }
+    }
+
+    records.sort_by_key(|(time, _)| *time);
+
+    return Ok(records);
+}
+
+// A price provider for replaying recorded prices, which only exists to configure the AlertService for backtesting,
+// as the recorded prices are passed to it directly.
+pub struct RecordedPriceProvider {
+    have_percent_change_1h: bool,
+}
+
+impl RecordedPriceProvider {
+    pub fn new(records: &PriceRecords) -> RecordedPriceProvider {
+        let have_percent_change_1h = records.iter().flat_map(|(_, prices)| prices).any(|p| p.percent_change_1h.is_some());
+        return RecordedPriceProvider { have_percent_change_1h };
+    }
+}
+
+impl PriceProvider for RecordedPriceProvider {
+    fn configure(&mut self, _params: &PriceProviderParams) -> Option<ConfigDetails> {
+        let mut config_details = ConfigDetails::new();
+        config_details.have_percent_change_1h = self.have_percent_change_1h;
+        return Some(config_details);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Duration;
+
+    #[test]
+    fn test_record_load_round_trip_01() {
+        let path = std::env::temp_dir().join(format!("cryptmon_price_record_test_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let coin = CoinPriceItem { watermarks_24h: Some(Watermarks::new(39000.0, 41000.0)), price_change_24h: -1200.0, percent_change_24h: -2.95,
+                                   volume_24h: Some(2.5e10), ..CoinPriceItem::new_basic("btc", "Bitcoin", 39500.0) };
+        let start_time = Local::now();
+        let recorder = PriceRecorder::new(Some(path.to_string()));
+        for minutes in [0, 2, 4] {
+            recorder.record(std::slice::from_ref(&coin), start_time + Duration::minutes(minutes));
+        }
+
+        let records = load_price_records(path, start_time - Duration::seconds(1), start_time + Duration::minutes(3)).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(records.len(), 2);
+        let loaded = &records[1].1[0];
+        assert_eq!(loaded.symbol, "btc");
+        assert_eq!(loaded.current_price, 39500.0);
+        assert_eq!(loaded.watermarks_24h.as_ref().map(|w| w.high), Some(41000.0));
+        assert_eq!(loaded.volume_24h, Some(2.5e10));
+    }
+}
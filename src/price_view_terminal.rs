@@ -13,7 +13,7 @@
  ---------
 */
 
-use crate::config::{Config, DisplayDataViewType};
+use crate::config::{Config, DisplayDataViewType, DisplaySortType};
 
 use crate::price_provider::{PriceProvider, PriceProviderParams, ConfigDetails, CoinPriceItem};
 
@@ -21,9 +21,13 @@ use crate::cli_table_printer::{CLITablePrinter, Alignment};
 
 use crate::formatting_helpers::{smart_format};
 
+use crate::scheduler::{FixedRateSchedule, duration_until};
+
 //use termion::{color};
 use chrono::{Local};
 
+use std::cmp::Ordering;
+
 pub struct PriceViewTerminal {
 
     // our copy of the config...
@@ -59,7 +63,10 @@ impl PriceViewTerminal {
         // Not amazingly happy about this, but I'm less happy with alternatives in this chicken-and-egg situation...
         self.price_provider_params.wanted_coin_symbols = self.config.display_config.wanted_coins.clone();
         let mut_provider = &mut self.price_provider;
-        mut_provider.configure(&self.price_provider_params);
+        if let Some(config_details) = mut_provider.configure(&self.price_provider_params) {
+            config_details.report_unresolved_symbols();
+            self.config_details = config_details;
+        }
 
         let price_heading = format!("Price ({})", self.config.display_config.fiat_currency.to_ascii_uppercase());
         self.table_headings = vec!["Sym".to_string(), "Name".to_string(), price_heading];
@@ -91,6 +98,10 @@ impl PriceViewTerminal {
 
         println!("Fetching prices...");
 
+        // so the updates happen at a fixed rate, regardless of how long fetching the prices takes
+        let schedule = FixedRateSchedule::new(self.config.display_config.update_period, self.config.display_config.align_to_minute,
+                                              Local::now());
+
         loop {
             let results = self.price_provider.get_current_prices();
 
@@ -108,14 +119,22 @@ impl PriceViewTerminal {
                 // clone a copy of table def to use..
                 // TODO: might want to just reset some contents of it, but then need to think about interior mutablility or something?
                 let mut local_table = self.table_def.clone();
-                for price in prices {
-                    self.add_coin_details_to_table(&mut local_table, &price);
+                let ordered_prices = order_price_items(prices, &self.config.display_config.wanted_coins,
+                                                        self.config.display_config.sort_type);
+                for (symbol, price) in ordered_prices {
+                    if let Some(price) = price {
+                        self.add_coin_details_to_table(&mut local_table, &price);
+                    }
+                    else {
+                        add_placeholder_row_to_table(&mut local_table, &symbol);
+                    }
                 }
 
                 println!("{}", local_table);
             }
 
-            std::thread::sleep(std::time::Duration::from_secs(self.config.display_config.update_period));
+            let now = Local::now();
+            std::thread::sleep(duration_until(now, schedule.next_tick_after(now)));
         }
     }
 
@@ -160,3 +179,85 @@ impl PriceViewTerminal {
         table_printer.add_row_strings(&row_strings);
     }
 }
+
+// for symbols we wanted but didn't get a price back for, so that they don't just silently vanish from the table
+fn add_placeholder_row_to_table(table_printer: &mut CLITablePrinter, symbol: &str) {
+    let symbol = symbol.to_ascii_uppercase();
+    let mut row_strings: Vec<&str> = vec![&symbol, "(not found)"];
+    while row_strings.len() < table_printer.get_num_columns() {
+        row_strings.push("-");
+    }
+
+    table_printer.add_row_strings(&row_strings);
+}
+
+// Returns the items in the wanted display order, with a None item for each wanted symbol (lowercase)
+// that wasn't present in the price results.
+// With the WantedCoins sort type, missing items keep their position, otherwise they're put at the end.
+fn order_price_items(prices: Vec<CoinPriceItem>, wanted_coins: &[String], sort_type: DisplaySortType) -> Vec<(String, Option<CoinPriceItem>)> {
+    let mut remaining_prices = prices;
+    let mut ordered_items = Vec::with_capacity(wanted_coins.len());
+
+    for symbol in wanted_coins {
+        let found_index = remaining_prices.iter().position(|p| p.symbol.eq_ignore_ascii_case(symbol));
+        let price = found_index.map(|index| remaining_prices.remove(index));
+        ordered_items.push((symbol.to_ascii_lowercase(), price));
+    }
+
+    // anything else the provider returned which we didn't explicitly ask for goes on the end
+    for price in remaining_prices {
+        ordered_items.push((price.symbol.to_ascii_lowercase(), Some(price)));
+    }
+
+    if sort_type == DisplaySortType::WantedCoins {
+        return ordered_items;
+    }
+
+    let (mut found_items, missing_items): (Vec<_>, Vec<_>) = ordered_items.into_iter().partition(|item| item.1.is_some());
+
+    found_items.sort_by(|a, b| {
+        let a = a.1.as_ref().unwrap();
+        let b = b.1.as_ref().unwrap();
+        match sort_type {
+            DisplaySortType::Symbol =>           a.symbol.cmp(&b.symbol),
+            DisplaySortType::Name =>             a.name.to_ascii_lowercase().cmp(&b.name.to_ascii_lowercase()),
+            DisplaySortType::Price =>            b.current_price.partial_cmp(&a.current_price).unwrap_or(Ordering::Equal),
+            DisplaySortType::PercentChange24h => b.percent_change_24h.partial_cmp(&a.percent_change_24h).unwrap_or(Ordering::Equal),
+            DisplaySortType::WantedCoins =>      Ordering::Equal,
+        }
+    });
+
+    found_items.extend(missing_items);
+
+    return found_items;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_price_items_01() {
+        let prices = vec![CoinPriceItem { percent_change_24h: 1.5, ..CoinPriceItem::new_basic("ETH", "Ethereum", 2800.0) },
+                          CoinPriceItem { percent_change_24h: 5.0, ..CoinPriceItem::new_basic("DOGE", "Dogecoin", 0.1) },
+                          CoinPriceItem { percent_change_24h: -2.0, ..CoinPriceItem::new_basic("BTC", "Bitcoin", 39500.0) }];
+        let wanted_coins: Vec<String> = ["btc", "xyz", "eth", "doge"].iter().map(|s| s.to_string()).collect();
+
+        let get_symbols = |sort_type: DisplaySortType| -> Vec<String> {
+            order_price_items(prices.clone(), &wanted_coins, sort_type).into_iter()
+                .map(|(symbol, price)| if price.is_some() { symbol } else { format!("({})", symbol) }).collect()
+        };
+
+        // missing symbols keep their position in the wanted order, otherwise they're at the end
+        assert_eq!(get_symbols(DisplaySortType::WantedCoins), vec!["btc", "(xyz)", "eth", "doge"]);
+        assert_eq!(get_symbols(DisplaySortType::Symbol), vec!["btc", "doge", "eth", "(xyz)"]);
+        assert_eq!(get_symbols(DisplaySortType::Name), vec!["btc", "doge", "eth", "(xyz)"]);
+        assert_eq!(get_symbols(DisplaySortType::Price), vec!["btc", "eth", "doge", "(xyz)"]);
+        assert_eq!(get_symbols(DisplaySortType::PercentChange24h), vec!["doge", "eth", "btc", "(xyz)"]);
+
+        // anything returned which wasn't wanted goes on the end
+        let wanted_coins = vec!["eth".to_string()];
+        let ordered_items = order_price_items(prices, &wanted_coins, DisplaySortType::WantedCoins);
+        assert_eq!(ordered_items.iter().map(|(symbol, _)| symbol.as_str()).collect::<Vec<&str>>(), vec!["eth", "doge", "btc"]);
+    }
+}
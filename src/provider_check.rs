@@ -0,0 +1,165 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use crate::config::{Config};
+
+use crate::price_provider::{PriceProviderParams, PRICE_PROVIDER_NAMES, create_price_provider};
+
+use crate::cli_table_printer::{CLITablePrinter, Alignment};
+
+use std::time::Instant;
+
+// The results of checking a single price provider, for the summary table.
+struct ProviderCheckResult {
+    name:                   String,
+    // whether it's the 'dataProvider' for either the display or alerts
+    in_use:                 bool,
+
+    configured:             bool,
+    configure_time_ms:      u128,
+
+    // None if we didn't get as far as requesting prices
+    prices_time_ms:         Option<u128>,
+    prices_error:           Option<String>,
+
+    api_key_status:         &'static str,
+
+    resolved_count:         usize,
+    unresolved_symbols:     Vec<String>,
+
+    have_percent_change_1h: bool,
+    have_price_change_24h:  bool,
+    have_watermarks_24h:    bool,
+}
+
+// Diagnostic command for checking each of the available price providers can be reached, is configured
+// correctly, and can resolve all of the wanted coins, printing the results as a table.
+pub fn run_providers_check(config: &Config) {
+    let wanted_coins = &config.display_config.wanted_coins;
+
+    let mut params = PriceProviderParams::new();
+    params.fiat_currency = config.display_config.fiat_currency.clone();
+    params.coin_name_ignore_items = config.display_config.coin_name_ignore_items.clone();
+    params.wanted_coin_symbols = wanted_coins.clone();
+
+    println!("Checking price providers with {} wanted coins in {}...\n", wanted_coins.len(), params.fiat_currency.to_ascii_uppercase());
+
+    let mut results = Vec::with_capacity(PRICE_PROVIDER_NAMES.len());
+
+    for provider_name in PRICE_PROVIDER_NAMES {
+        let in_use = config.display_config.data_provider == provider_name || config.alert_config.data_provider == provider_name;
+        results.push(check_provider(provider_name, in_use, &params, wanted_coins));
+    }
+
+    let mut table = CLITablePrinter::new(6);
+    table.add_titles(["Provider", "In use", "Status", "API key", "Latency", "Resolved"]);
+    table.set_alignment_multiple(&[4usize, 5usize], Alignment::Right);
+    table.add_column_def("% chng 1h", Alignment::Left);
+    table.add_column_def("chng 24h", Alignment::Left);
+    table.add_column_def("low/high 24h", Alignment::Left);
+
+    for result in &results {
+        let status = if !result.configured { "config failed" } else if result.prices_error.is_some() { "prices failed" } else { "OK" };
+        let latency = match result.prices_time_ms {
+            Some(prices_time) => format!("{}ms / {}ms", result.configure_time_ms, prices_time),
+            None =>              format!("{}ms / -", result.configure_time_ms),
+        };
+        let resolved = format!("{}/{}", result.resolved_count, wanted_coins.len());
+
+        // we only know the capabilities if the provider could be configured
+        let capability = |val: bool| if result.configured { yes_no(val) } else { "-" };
+
+        table.add_row_strings(&[&result.name, yes_no(result.in_use), status, result.api_key_status, &latency, &resolved,
+                                 capability(result.have_percent_change_1h), capability(result.have_price_change_24h),
+                                 capability(result.have_watermarks_24h)]);
+    }
+
+    println!("{}", table);
+    println!("Latency is the time to configure (i.e. look up coin lists) / the time to fetch current prices.\n");
+
+    for result in &results {
+        if let Some(err) = &result.prices_error {
+            println!("{}: error getting prices: {}", result.name, err);
+        }
+        if !result.unresolved_symbols.is_empty() {
+            let symbols: Vec<String> = result.unresolved_symbols.iter().map(|s| s.to_ascii_uppercase()).collect();
+            println!("{}: couldn't resolve: {}", result.name, symbols.join(", "));
+        }
+    }
+}
+
+fn check_provider(provider_name: &str, in_use: bool, params: &PriceProviderParams, wanted_coins: &[String]) -> ProviderCheckResult {
+    let mut result = ProviderCheckResult { name: provider_name.to_string(), in_use, configured: false, configure_time_ms: 0,
+                                           prices_time_ms: None, prices_error: None, api_key_status: "n/a",
+                                           resolved_count: 0, unresolved_symbols: Vec::with_capacity(0),
+                                           have_percent_change_1h: false, have_price_change_24h: false,
+                                           have_watermarks_24h: false };
+
+    let needs_api_key = provider_name == "coinmarketcap";
+    if needs_api_key {
+        let have_key = std::env::var_os("COINMARKETCAP_API_KEY").map(|k| !k.is_empty()).unwrap_or(false);
+        result.api_key_status = if have_key { "present" } else { "missing" };
+    }
+
+    let configure_start = Instant::now();
+    let provider = create_price_provider(provider_name, params);
+    result.configure_time_ms = configure_start.elapsed().as_millis();
+
+    if provider.is_none() {
+        result.unresolved_symbols = wanted_coins.to_vec();
+        return result;
+    }
+
+    let (provider, config_details) = provider.unwrap();
+    result.configured = true;
+    result.have_percent_change_1h = config_details.have_percent_change_1h;
+    result.have_price_change_24h = config_details.have_price_change_24h;
+    result.have_watermarks_24h = config_details.have_watermarks_24h;
+
+    let prices_start = Instant::now();
+    let prices = provider.get_current_prices();
+    result.prices_time_ms = Some(prices_start.elapsed().as_millis());
+
+    match prices {
+        Ok(prices) => {
+            if needs_api_key {
+                result.api_key_status = "valid";
+            }
+
+            for symbol in wanted_coins {
+                if prices.iter().any(|p| p.symbol.eq_ignore_ascii_case(symbol)) {
+                    result.resolved_count += 1;
+                }
+                else {
+                    result.unresolved_symbols.push(symbol.to_ascii_lowercase());
+                }
+            }
+        },
+        Err(err) => {
+            if needs_api_key {
+                // we can't really tell for sure, but it's the most likely cause if we could configure it fine...
+                result.api_key_status = "invalid?";
+            }
+            result.prices_error = Some(err.to_string());
+            result.unresolved_symbols = config_details.unresolved_symbols;
+        }
+    }
+
+    return result;
+}
+
+fn yes_no(val: bool) -> &'static str {
+    if val { "yes" } else { "no" }
+}
@@ -0,0 +1,91 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+
+use chrono::{DateTime, Duration, Local, TimeZone};
+
+// A fixed-rate schedule, where the ticks are at fixed intervals from an anchor time, so that the time taken
+// to fetch prices (or anything else) between ticks doesn't make the period drift.
+// If a tick is missed because something took longer than the period, it's skipped rather than being run late.
+#[derive(Clone, Debug)]
+pub struct FixedRateSchedule {
+    // in seconds
+    period:     i64,
+    anchor:     DateTime<Local>,
+}
+
+impl FixedRateSchedule {
+    // if align_to_minute is set, the ticks are aligned to minute boundaries (and to multiples of the
+    // period within the day for periods of whole minutes, i.e. 5m ticks at :00, :05, :10, ...),
+    // otherwise they're relative to the start time.
+    pub fn new(period: u64, align_to_minute: bool, start: DateTime<Local>) -> FixedRateSchedule {
+        let period = period.max(1) as i64;
+
+        let mut anchor = start;
+        if align_to_minute {
+            // work in local time, so that hour periods are aligned to local hours with timezones with partial hour offsets
+            let offset = start.offset().local_minus_utc() as i64;
+            let local_secs = start.timestamp() + offset;
+            let alignment = if period % 60 == 0 { period } else { 60 };
+            let aligned_secs = local_secs - local_secs.rem_euclid(alignment);
+            anchor = Local.timestamp_opt(aligned_secs - offset, 0).unwrap();
+        }
+
+        return FixedRateSchedule { period, anchor };
+    }
+
+    // the first tick strictly after the given time, skipping any which have been missed
+    pub fn next_tick_after(&self, time: DateTime<Local>) -> DateTime<Local> {
+        let elapsed_ms = (time - self.anchor).num_milliseconds();
+        let period_ms = self.period * 1000;
+        let ticks = elapsed_ms.div_euclid(period_ms) + 1;
+        return self.anchor + Duration::milliseconds(ticks * period_ms);
+    }
+}
+
+// how long to sleep for from 'now' until 'time', which is zero if it's already passed
+pub fn duration_until(now: DateTime<Local>, time: DateTime<Local>) -> std::time::Duration {
+    return (time - now).to_std().unwrap_or(std::time::Duration::ZERO);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_fixed_rate_schedule_01() {
+        let make_time = |h: u32, m: u32, s: u32| Local.from_local_datetime(&NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()
+                                                                            .and_hms_opt(h, m, s).unwrap()).unwrap();
+
+        let schedule = FixedRateSchedule::new(120, false, make_time(12, 0, 17));
+        assert_eq!(schedule.next_tick_after(make_time(12, 0, 17)), make_time(12, 2, 17));
+        // a slow fetch doesn't delay the next tick
+        assert_eq!(schedule.next_tick_after(make_time(12, 2, 21)), make_time(12, 4, 17));
+        // and missed ticks are skipped
+        assert_eq!(schedule.next_tick_after(make_time(12, 9, 0)), make_time(12, 10, 17));
+
+        let schedule = FixedRateSchedule::new(300, true, make_time(12, 3, 17));
+        assert_eq!(schedule.next_tick_after(make_time(12, 3, 17)), make_time(12, 5, 0));
+        assert_eq!(schedule.next_tick_after(make_time(12, 5, 0)), make_time(12, 10, 0));
+
+        // periods which aren't whole minutes are just aligned to the start of the minute
+        let schedule = FixedRateSchedule::new(90, true, make_time(12, 3, 17));
+        assert_eq!(schedule.next_tick_after(make_time(12, 3, 17)), make_time(12, 4, 30));
+
+        assert_eq!(duration_until(make_time(12, 4, 30), make_time(12, 3, 17)), std::time::Duration::ZERO);
+    }
+}
@@ -0,0 +1,118 @@
+/*
+ Cryptmon
+ Copyright 2022 Peter Pearson.
+ Licensed under the Apache License, Version 2.0 (the "License");
+ You may not use this file except in compliance with the License.
+ You may obtain a copy of the License at
+ http://www.apache.org/licenses/LICENSE-2.0
+ Unless required by applicable law or agreed to in writing, software
+ distributed under the License is distributed on an "AS IS" BASIS,
+ WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ See the License for the specific language governing permissions and
+ limitations under the License.
+ ---------
+*/
+
+use chrono::{DateTime, Local, FixedOffset, NaiveTime};
+
+// A daily time-of-day window, i.e. "23:00-07:00", which can wrap around midnight.
+// The start time is inclusive, and the end time exclusive.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TimeWindow {
+    pub start:      NaiveTime,
+    pub end:        NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn parse(str_val: &str) -> Option<TimeWindow> {
+        let (start_str, end_str) = str_val.split_once('-')?;
+        let start = NaiveTime::parse_from_str(start_str.trim(), "%H:%M").ok()?;
+        let end = NaiveTime::parse_from_str(end_str.trim(), "%H:%M").ok()?;
+
+        return Some(TimeWindow { start, end });
+    }
+
+    pub fn contains(&self, time_of_day: NaiveTime) -> bool {
+        if self.start <= self.end {
+            return time_of_day >= self.start && time_of_day < self.end;
+        }
+
+        // it wraps around midnight
+        return time_of_day >= self.start || time_of_day < self.end;
+    }
+}
+
+// Parses a timezone for time windows, either "local", "UTC", or a fixed offset from UTC, i.e. "+12:00".
+// Returns Some(None) for the local timezone.
+pub fn parse_timezone(str_val: &str) -> Option<Option<FixedOffset>> {
+    if str_val.eq_ignore_ascii_case("local") {
+        return Some(None);
+    }
+    if str_val.eq_ignore_ascii_case("utc") {
+        return Some(FixedOffset::east_opt(0));
+    }
+
+    let sign = match str_val.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _   => return None,
+    };
+    let (hours_str, mins_str) = str_val[1..].split_once(':').unwrap_or((&str_val[1..], "0"));
+    // Note: u32 parsing allows a leading '+', so check they're only digits
+    if [hours_str, mins_str].iter().any(|s| s.is_empty() || !s.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    let hours = hours_str.parse::<u32>().ok()?;
+    let mins = mins_str.parse::<u32>().ok()?;
+    if hours > 23 || mins >= 60 {
+        return None;
+    }
+
+    return Some(Some(FixedOffset::east_opt(sign * (hours * 60 * 60 + mins * 60) as i32)?));
+}
+
+// the time of day of the given time in the timezone, or local time if that's None
+pub fn get_time_of_day(time: DateTime<Local>, timezone: Option<FixedOffset>) -> NaiveTime {
+    if let Some(offset) = timezone {
+        return time.with_timezone(&offset).time();
+    }
+    return time.time();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_window_01() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        let overnight = TimeWindow::parse("23:00-07:00").unwrap();
+        assert!(overnight.contains(time(23, 0)));
+        assert!(overnight.contains(time(3, 0)));
+        assert!(!overnight.contains(time(7, 0)));
+        assert!(!overnight.contains(time(12, 0)));
+
+        let daytime = TimeWindow::parse("09:00 - 17:30").unwrap();
+        assert!(daytime.contains(time(17, 29)));
+        assert!(!daytime.contains(time(8, 59)));
+
+        assert!(TimeWindow::parse("9am-5pm").is_none());
+    }
+
+    #[test]
+    fn test_parse_timezone_01() {
+        assert_eq!(parse_timezone("local"), Some(None));
+        assert_eq!(parse_timezone("UTC"), Some(FixedOffset::east_opt(0)));
+        assert_eq!(parse_timezone("+12:00"), Some(FixedOffset::east_opt(12 * 60 * 60)));
+        assert_eq!(parse_timezone("-05:30"), Some(FixedOffset::west_opt(5 * 60 * 60 + 30 * 60)));
+        assert_eq!(parse_timezone("+5"), Some(FixedOffset::east_opt(5 * 60 * 60)));
+        assert_eq!(parse_timezone("Pacific/Auckland"), None);
+        assert_eq!(parse_timezone("+999999:00"), None);
+        assert_eq!(parse_timezone("+24:00"), None);
+        assert_eq!(parse_timezone("-5:-30"), None);
+        assert_eq!(parse_timezone("--5"), None);
+        assert_eq!(parse_timezone("++5"), None);
+        assert_eq!(parse_timezone("+"), None);
+    }
+}